@@ -0,0 +1,565 @@
+//! AMF0 values used by FLV script data tags.
+
+use crate::amf3;
+use crate::error::{Error, ParseError};
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// AMF0 type markers
+pub mod marker {
+    pub const NUMBER: u8 = 0x00;
+    pub const BOOLEAN: u8 = 0x01;
+    pub const STRING: u8 = 0x02;
+    pub const OBJECT: u8 = 0x03;
+    pub const MOVIE_CLIP: u8 = 0x04;
+    pub const NULL: u8 = 0x05;
+    pub const UNDEFINED: u8 = 0x06;
+    pub const REFERENCE: u8 = 0x07;
+    pub const ECMA_ARRAY: u8 = 0x08;
+    pub const OBJECT_END: u8 = 0x09;
+    pub const STRICT_ARRAY: u8 = 0x0A;
+    pub const DATE: u8 = 0x0B;
+    pub const LONG_STRING: u8 = 0x0C;
+    pub const UNSUPPORTED: u8 = 0x0D;
+    pub const RECORD_SET: u8 = 0x0E;
+    pub const XML_DOCUMENT: u8 = 0x0F;
+    pub const TYPED_OBJECT: u8 = 0x10;
+    pub const AVMPLUS_OBJECT: u8 = 0x11;
+}
+
+cfg_serde! {
+    mod de;
+    mod ser;
+
+    pub use self::de::{from_slice, from_value};
+    pub use self::ser::{to_value, to_vec};
+
+    /// An AMF0 date, to be used as a field of the types serialized with
+    /// [`to_vec`] and deserialized with [`from_slice`]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Date {
+        /// milliseconds since 1970-01-01 00:00:00 UTC
+        pub millis: f64,
+        /// reserved, should be 0
+        pub time_zone: i16,
+    }
+}
+
+/// Name of the newtype struct a [`Date`] is serialized as, so that it is told
+/// apart from a tuple
+#[cfg(feature = "serde")]
+const DATE_NAME: &str = "$flv::amf0::Date";
+
+/// A property list of an object or an ECMA array, in wire order
+pub type Properties = Vec<(String, Value)>;
+
+/// How deep objects and arrays may be nested in the data read by
+/// [`Value::read`]
+pub const MAX_DEPTH: usize = 64;
+
+/// Longest property name or class name in bytes
+pub const MAX_KEY_LEN: usize = u16::MAX as usize;
+
+/// AMF0 value
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Value {
+    Number(f64),
+    Boolean(bool),
+    String(String),
+    Object(Properties),
+    Null,
+    Undefined,
+    Reference(u16),
+    EcmaArray(Properties),
+    StrictArray(Vec<Value>),
+    Date {
+        /// milliseconds since 1970-01-01 00:00:00 UTC
+        millis: f64,
+        /// reserved, should be 0
+        time_zone: i16,
+    },
+    LongString(String),
+    Unsupported,
+    XmlDocument(String),
+    TypedObject {
+        class_name: String,
+        properties: Properties,
+    },
+    /// An AMF3 value, after an `avmplus-object` marker. Each one is read and
+    /// written with its own AMF3 reference tables. The `as_*` accessors look
+    /// into it.
+    Avmplus(amf3::Value),
+}
+
+impl Value {
+    /// Reads one value from the front of `buf` and advances it.
+    ///
+    /// Objects and arrays nested deeper than [`MAX_DEPTH`] are rejected.
+    pub fn read(buf: &mut &[u8]) -> Result<Self, ParseError> {
+        Self::read_nested(buf, 0)
+    }
+
+    fn read_nested(buf: &mut &[u8], depth: usize) -> Result<Self, ParseError> {
+        use Value::*;
+
+        let marker = read_u8(buf)?;
+        let nested = matches!(
+            marker,
+            marker::OBJECT | marker::ECMA_ARRAY | marker::STRICT_ARRAY | marker::TYPED_OBJECT
+        );
+        if nested && depth >= MAX_DEPTH {
+            return Err(ParseError::Amf0Depth);
+        }
+
+        Ok(match marker {
+            marker::NUMBER => Number(read_f64(buf)?),
+            marker::BOOLEAN => Boolean(read_u8(buf)? != 0),
+            marker::STRING => String(read_string(buf)?),
+            marker::OBJECT => Object(read_properties(buf, depth + 1)?),
+            marker::NULL => Null,
+            marker::UNDEFINED => Undefined,
+            marker::REFERENCE => Reference(read_u16(buf)?),
+            marker::ECMA_ARRAY => {
+                // the associative count is only a hint, the list is terminated
+                // by an object end marker (which some muxers omit at the end of
+                // the tag)
+                let _count = read_u32(buf)?;
+                EcmaArray(read_properties(buf, depth + 1)?)
+            }
+            marker::STRICT_ARRAY => {
+                let count = read_u32(buf)? as usize;
+                let mut values = Vec::with_capacity(count.min(buf.len()));
+                for _ in 0..count {
+                    values.push(Value::read_nested(buf, depth + 1)?);
+                }
+                StrictArray(values)
+            }
+            marker::DATE => {
+                let millis = read_f64(buf)?;
+                let time_zone = read_u16(buf)? as i16;
+                Date { millis, time_zone }
+            }
+            marker::LONG_STRING => LongString(read_long_string(buf)?),
+            marker::UNSUPPORTED => Unsupported,
+            marker::XML_DOCUMENT => XmlDocument(read_long_string(buf)?),
+            marker::TYPED_OBJECT => {
+                let class_name = read_string(buf)?;
+                let properties = read_properties(buf, depth + 1)?;
+                TypedObject {
+                    class_name,
+                    properties,
+                }
+            }
+            marker::AVMPLUS_OBJECT => Avmplus(amf3::Value::read(buf)?),
+            n => return Err(ParseError::Amf0Marker(n)),
+        })
+    }
+
+    /// Appends the encoded value to `buf`.
+    ///
+    /// Fails with [`Error::DataSize`] if a property name or a class name is
+    /// longer than [`MAX_KEY_LEN`], which the format can't represent.
+    pub fn write(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+        use Value::*;
+
+        match self {
+            Number(n) => {
+                buf.push(marker::NUMBER);
+                buf.extend_from_slice(&n.to_be_bytes());
+            }
+            Boolean(b) => {
+                buf.push(marker::BOOLEAN);
+                buf.push(*b as u8);
+            }
+            String(s) if s.len() > MAX_KEY_LEN => {
+                buf.push(marker::LONG_STRING);
+                write_long_string(buf, s);
+            }
+            String(s) => {
+                buf.push(marker::STRING);
+                write_string(buf, s)?;
+            }
+            Object(properties) => {
+                buf.push(marker::OBJECT);
+                write_properties(buf, properties)?;
+            }
+            Null => buf.push(marker::NULL),
+            Undefined => buf.push(marker::UNDEFINED),
+            Reference(r) => {
+                buf.push(marker::REFERENCE);
+                buf.extend_from_slice(&r.to_be_bytes());
+            }
+            EcmaArray(properties) => {
+                buf.push(marker::ECMA_ARRAY);
+                buf.extend_from_slice(&(properties.len() as u32).to_be_bytes());
+                write_properties(buf, properties)?;
+            }
+            StrictArray(values) => {
+                buf.push(marker::STRICT_ARRAY);
+                buf.extend_from_slice(&(values.len() as u32).to_be_bytes());
+                for value in values {
+                    value.write(buf)?;
+                }
+            }
+            Date { millis, time_zone } => {
+                buf.push(marker::DATE);
+                buf.extend_from_slice(&millis.to_be_bytes());
+                buf.extend_from_slice(&time_zone.to_be_bytes());
+            }
+            LongString(s) => {
+                buf.push(marker::LONG_STRING);
+                write_long_string(buf, s);
+            }
+            Unsupported => buf.push(marker::UNSUPPORTED),
+            XmlDocument(s) => {
+                buf.push(marker::XML_DOCUMENT);
+                write_long_string(buf, s);
+            }
+            TypedObject {
+                class_name,
+                properties,
+            } => {
+                buf.push(marker::TYPED_OBJECT);
+                write_string(buf, class_name)?;
+                write_properties(buf, properties)?;
+            }
+            Avmplus(value) => {
+                buf.push(marker::AVMPLUS_OBJECT);
+                value.write(buf);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::Avmplus(value) => value.as_number(),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(b) => Some(*b),
+            Value::Avmplus(value) => value.as_bool(),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) | Value::LongString(s) => Some(s),
+            Value::Avmplus(value) => value.as_str(),
+            _ => None,
+        }
+    }
+
+    /// Properties of an object, a typed object or an ECMA array
+    pub fn properties(&self) -> Option<&[(String, Value)]> {
+        match self {
+            Value::Object(properties)
+            | Value::EcmaArray(properties)
+            | Value::TypedObject { properties, .. } => Some(properties),
+            _ => None,
+        }
+    }
+
+    /// Looks up a property by name, see [`Value::properties`]
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.properties()?
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    }
+}
+
+/// Decodes all values in `data`.
+pub fn decode(mut data: &[u8]) -> Result<Vec<Value>, ParseError> {
+    let mut values = vec![];
+    while !data.is_empty() {
+        values.push(Value::read(&mut data)?);
+    }
+    Ok(values)
+}
+
+/// Encodes `values` one after another, see [`Value::write`].
+pub fn encode(values: &[Value]) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![];
+    for value in values {
+        value.write(&mut buf)?;
+    }
+    Ok(buf)
+}
+
+pub(crate) fn take<'a>(buf: &mut &'a [u8], n: usize) -> Result<&'a [u8], ParseError> {
+    if buf.len() < n {
+        return Err(ParseError::Amf0Eof);
+    }
+    let (head, tail) = buf.split_at(n);
+    *buf = tail;
+    Ok(head)
+}
+
+pub(crate) fn read_u8(buf: &mut &[u8]) -> Result<u8, ParseError> {
+    Ok(take(buf, 1)?[0])
+}
+
+fn read_u16(buf: &mut &[u8]) -> Result<u16, ParseError> {
+    let b = take(buf, 2)?;
+    Ok(u16::from_be_bytes([b[0], b[1]]))
+}
+
+pub(crate) fn read_u32(buf: &mut &[u8]) -> Result<u32, ParseError> {
+    let b = take(buf, 4)?;
+    Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+pub(crate) fn read_f64(buf: &mut &[u8]) -> Result<f64, ParseError> {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(take(buf, 8)?);
+    Ok(f64::from_be_bytes(bytes))
+}
+
+pub(crate) fn read_utf8(buf: &mut &[u8], len: usize) -> Result<String, ParseError> {
+    let bytes = take(buf, len)?;
+    core::str::from_utf8(bytes)
+        .map(ToOwned::to_owned)
+        .map_err(|_| ParseError::Amf0String)
+}
+
+fn read_string(buf: &mut &[u8]) -> Result<String, ParseError> {
+    let len = read_u16(buf)? as usize;
+    read_utf8(buf, len)
+}
+
+fn read_long_string(buf: &mut &[u8]) -> Result<String, ParseError> {
+    let len = read_u32(buf)? as usize;
+    read_utf8(buf, len)
+}
+
+fn read_properties(buf: &mut &[u8], depth: usize) -> Result<Properties, ParseError> {
+    let mut properties = vec![];
+    loop {
+        if buf.is_empty() {
+            // tolerate a missing object end marker at the end of the data
+            return Ok(properties);
+        }
+
+        let key = read_string(buf)?;
+        if key.is_empty() && buf.first() == Some(&marker::OBJECT_END) {
+            *buf = &buf[1..];
+            return Ok(properties);
+        }
+
+        let value = Value::read_nested(buf, depth)?;
+        properties.push((key, value));
+    }
+}
+
+fn write_string(buf: &mut Vec<u8>, s: &str) -> Result<(), Error> {
+    if s.len() > MAX_KEY_LEN {
+        return Err(Error::DataSize(s.len()));
+    }
+    buf.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    buf.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+fn write_long_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u32).to_be_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn write_properties(buf: &mut Vec<u8>, properties: &[(String, Value)]) -> Result<(), Error> {
+    for (key, value) in properties {
+        write_string(buf, key)?;
+        value.write(buf)?;
+    }
+    buf.extend_from_slice(&[0, 0, marker::OBJECT_END]);
+    Ok(())
+}
+
+#[test]
+fn amf0_round_trip() {
+    let values = vec![
+        Value::String("onMetaData".to_owned()),
+        Value::EcmaArray(vec![
+            ("duration".to_owned(), Value::Number(12.5)),
+            ("stereo".to_owned(), Value::Boolean(true)),
+            (
+                "keyframes".to_owned(),
+                Value::Object(vec![(
+                    "times".to_owned(),
+                    Value::StrictArray(vec![Value::Number(0.0), Value::Number(2.0)]),
+                )]),
+            ),
+            (
+                "created".to_owned(),
+                Value::Date {
+                    millis: 1.6e12,
+                    time_zone: 0,
+                },
+            ),
+            ("encoder".to_owned(), Value::Null),
+            (
+                "width".to_owned(),
+                Value::Avmplus(amf3::Value::Integer(1280)),
+            ),
+        ]),
+    ];
+
+    let bytes = encode(&values).unwrap();
+
+    assert_eq!(Ok(values.clone()), decode(&bytes));
+    assert_eq!(
+        Some(1280.0),
+        values[1].get("width").and_then(Value::as_number)
+    );
+    assert_eq!(Err(ParseError::Amf0Eof), decode(&bytes[..bytes.len() - 20]));
+}
+
+#[test]
+fn amf0_long_names() {
+    let name = "x".repeat(MAX_KEY_LEN + 1);
+    let object = Value::Object(vec![(name.clone(), Value::Null)]);
+    assert!(matches!(encode(&[object]), Err(Error::DataSize(n)) if n == name.len()));
+
+    let typed = Value::TypedObject {
+        class_name: name.clone(),
+        properties: vec![],
+    };
+    assert!(matches!(encode(&[typed]), Err(Error::DataSize(_))));
+
+    // long string values are written as long strings
+    let bytes = encode(&[Value::String(name.clone())]).unwrap();
+    assert_eq!(Ok(vec![Value::LongString(name)]), decode(&bytes));
+}
+
+#[test]
+fn amf0_nesting_limit() {
+    let nested = |depth: usize| {
+        let mut bytes = vec![];
+        for _ in 0..depth {
+            bytes.extend_from_slice(&[marker::STRICT_ARRAY, 0, 0, 0, 1]);
+        }
+        bytes.push(marker::NULL);
+        bytes
+    };
+
+    assert!(decode(&nested(MAX_DEPTH)).is_ok());
+    assert_eq!(Err(ParseError::Amf0Depth), decode(&nested(MAX_DEPTH + 1)));
+    assert_eq!(Err(ParseError::Amf0Depth), decode(&nested(1 << 20)));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_script_data() {
+    use alloc::collections::BTreeMap;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Source {
+        Camera,
+        File { name: String },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct UserData {
+        id: u32,
+        offset: i16,
+        tags: Vec<String>,
+        created: Date,
+        counters: BTreeMap<String, u64>,
+        note: Option<String>,
+        sources: (Source, Source),
+    }
+
+    let data = UserData {
+        id: 7,
+        offset: -2,
+        tags: vec!["live".to_owned()],
+        created: Date {
+            millis: 1.6e12,
+            time_zone: 0,
+        },
+        counters: vec![("frames".to_owned(), 250)].into_iter().collect(),
+        note: None,
+        sources: (
+            Source::Camera,
+            Source::File {
+                name: "a.flv".to_owned(),
+            },
+        ),
+    };
+
+    let bytes = to_vec(&data).unwrap();
+    let value = Value::read(&mut &bytes[..]).unwrap();
+    assert_eq!(Some(7.0), value.get("id").and_then(Value::as_number));
+    assert_eq!(
+        Some(&Value::Date {
+            millis: 1.6e12,
+            time_zone: 0
+        }),
+        value.get("created")
+    );
+    assert!(matches!(value.get("counters"), Some(Value::EcmaArray(_))));
+    assert_eq!(Some(&Value::Null), value.get("note"));
+    assert_eq!(data, from_slice(&bytes).unwrap());
+
+    // an onFI tag body: the name, then an ECMA array
+    let body = encode(&[
+        Value::String("onFI".to_owned()),
+        Value::EcmaArray(vec![
+            ("sd".to_owned(), Value::String("18-10-2026".to_owned())),
+            ("st".to_owned(), Value::String("14:50:37.120".to_owned())),
+        ]),
+    ])
+    .unwrap();
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct OnFi {
+        sd: String,
+        st: String,
+    }
+    let mut data = &body[..];
+    assert_eq!(Ok(Value::String("onFI".to_owned())), Value::read(&mut data));
+    let fi: OnFi = from_slice(data).unwrap();
+    assert_eq!("14:50:37.120", fi.st);
+
+    // the same message in AMF3
+    let object = amf3::Object {
+        sealed: vec![
+            (
+                "sd".to_owned(),
+                amf3::Value::String("18-10-2026".to_owned()),
+            ),
+            (
+                "st".to_owned(),
+                amf3::Value::String("14:50:37.120".to_owned()),
+            ),
+        ],
+        ..amf3::Object::default()
+    };
+    let bytes = encode(&[Value::Avmplus(amf3::Value::Object(object))]).unwrap();
+    assert_eq!(fi, from_slice(&bytes).unwrap());
+
+    assert!(from_slice::<u32>(&to_vec(&1.5).unwrap()).is_err());
+    assert!(from_slice::<OnFi>(&body).is_err());
+
+    // integers at the edges of the u64 and i64 ranges
+    let below = 18446744073709549568.0; // largest f64 below 2^64
+    assert_eq!(
+        below as u64,
+        from_value::<u64>(Value::Number(below)).unwrap()
+    );
+    assert!(from_value::<u64>(Value::Number(18446744073709551616.0)).is_err());
+    assert_eq!(
+        i64::MIN,
+        from_value::<i64>(Value::Number(-9223372036854775808.0)).unwrap()
+    );
+    assert!(from_value::<i64>(Value::Number(-9223372036854777856.0)).is_err());
+}
@@ -0,0 +1,343 @@
+//! Deserialization of AMF0 into Rust values.
+//!
+//! The input is first read as a [`Value`], which is itself a deserializer.
+//! Objects, typed objects and ECMA arrays are read as maps or structs,
+//! strict arrays as sequences, null and undefined as unit or `None`, and
+//! dates as their number of milliseconds unless a [`Date`] is expected.
+//! Numbers are read as integers when they have no fractional part.
+//!
+//! Embedded AMF3 values are read the same way, byte arrays and vectors being
+//! sequences, dictionaries maps, and arrays maps when they have named
+//! members, the indexed members then being named after their index.
+
+use super::{Date, Value, DATE_NAME};
+use crate::amf3;
+use crate::error::{Error, Result};
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{
+    self, DeserializeOwned, Deserializer, IntoDeserializer, Unexpected, VariantAccess, Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+/// Decodes a single AMF0 value, the whole of `data`.
+pub fn from_slice<T: DeserializeOwned>(mut data: &[u8]) -> Result<T> {
+    let value = Value::read(&mut data)?;
+    if !data.is_empty() {
+        return Err(de::Error::custom("trailing data after the AMF0 value"));
+    }
+
+    from_value(value)
+}
+
+/// Converts an AMF0 [`Value`] into a `T`.
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T> {
+    T::deserialize(value)
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Serde(msg.to_string())
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Date {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+        struct DateVisitor;
+
+        impl<'de> Visitor<'de> for DateVisitor {
+            type Value = Date;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an AMF0 date")
+            }
+
+            fn visit_newtype_struct<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> core::result::Result<Date, D::Error> {
+                let (millis, time_zone) = de::Deserialize::deserialize(deserializer)?;
+                Ok(Date { millis, time_zone })
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(DATE_NAME, DateVisitor)
+    }
+}
+
+impl Value {
+    fn unexpected(&self) -> Unexpected<'_> {
+        match self {
+            Value::Number(n) | Value::Date { millis: n, .. } => Unexpected::Float(*n),
+            Value::Boolean(b) => Unexpected::Bool(*b),
+            Value::String(s) | Value::LongString(s) | Value::XmlDocument(s) => Unexpected::Str(s),
+            Value::Object(_) | Value::EcmaArray(_) | Value::TypedObject { .. } => Unexpected::Map,
+            Value::StrictArray(_) => Unexpected::Seq,
+            Value::Null | Value::Undefined | Value::Unsupported => Unexpected::Unit,
+            Value::Reference(_) => Unexpected::Other("AMF0 reference"),
+            Value::Avmplus(_) => Unexpected::Other("AMF3 value"),
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Integers are visited as such when the number has no fractional part and
+/// fits in an `i64` or a `u64`.
+macro_rules! deserialize_integer {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                match self.as_number() {
+                    Some(n) if n == n as i64 as f64 && n < 0.0 => visitor.visit_i64(n as i64),
+                    // `u64::MAX as f64` rounds up to 2^64, which doesn't fit
+                    Some(n) if n == n as u64 as f64 && n < 18446744073709551616.0 => {
+                        visitor.visit_u64(n as u64)
+                    }
+                    _ => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Value::Number(n) | Value::Date { millis: n, .. } => visitor.visit_f64(n),
+            Value::Boolean(b) => visitor.visit_bool(b),
+            Value::String(s) | Value::LongString(s) | Value::XmlDocument(s) => {
+                visitor.visit_string(s)
+            }
+            Value::Object(properties)
+            | Value::EcmaArray(properties)
+            | Value::TypedObject { properties, .. } => visit_map(properties, visitor),
+            Value::StrictArray(values) => visit_seq(values, visitor),
+            Value::Null | Value::Undefined | Value::Unsupported => visitor.visit_unit(),
+            value @ Value::Reference(_) => {
+                Err(de::Error::invalid_type(value.unexpected(), &visitor))
+            }
+            Value::Avmplus(value) => value.deserialize_any(visitor),
+        }
+    }
+
+    deserialize_integer! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Value::Null | Value::Undefined => visitor.visit_none(),
+            Value::Avmplus(value) => value.deserialize_option(visitor),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        if name != DATE_NAME {
+            return visitor.visit_newtype_struct(self);
+        }
+
+        match self {
+            Value::Date { millis, time_zone } => {
+                visitor.visit_newtype_struct(Value::StrictArray(vec![
+                    Value::Number(millis),
+                    Value::Number(time_zone.into()),
+                ]))
+            }
+            Value::Avmplus(amf3::Value::Date(millis)) => {
+                visitor.visit_newtype_struct(Value::StrictArray(vec![
+                    Value::Number(millis),
+                    Value::Number(0.0),
+                ]))
+            }
+            value => Err(de::Error::invalid_type(value.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self {
+            Value::String(variant) => visitor.visit_enum(Enum {
+                variant,
+                value: None,
+            }),
+            Value::Object(mut properties) if properties.len() == 1 => {
+                let (variant, value) = properties.remove(0);
+                visitor.visit_enum(Enum {
+                    variant,
+                    value: Some(value),
+                })
+            }
+            value => Err(de::Error::invalid_type(value.unexpected(), &visitor)),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool f32 f64 char str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for amf3::Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for amf3::Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        use amf3::Value::*;
+
+        match self {
+            Undefined | Null => visitor.visit_unit(),
+            Boolean(b) => visitor.visit_bool(b),
+            Integer(n) => visitor.visit_i32(n),
+            Double(n) | Date(n) => visitor.visit_f64(n),
+            String(s) | XmlDocument(s) | Xml(s) => visitor.visit_string(s),
+            Array { associative, dense } if associative.is_empty() => visit_seq(dense, visitor),
+            Array {
+                mut associative,
+                dense,
+            } => {
+                let indexed = dense.into_iter().enumerate();
+                associative.extend(indexed.map(|(i, value)| (i.to_string(), value)));
+                visit_map(associative, visitor)
+            }
+            Object(object) => {
+                let mut properties = object.sealed;
+                properties.extend(object.dynamic.unwrap_or_default());
+                visit_map(properties, visitor)
+            }
+            Externalizable { value, .. } => value.deserialize_any(visitor),
+            ByteArray(bytes) => visit_seq(bytes, visitor),
+            VectorInt { values, .. } => visit_seq(values, visitor),
+            VectorUint { values, .. } => visit_seq(values, visitor),
+            VectorDouble { values, .. } => visit_seq(values, visitor),
+            VectorObject { values, .. } => visit_seq(values, visitor),
+            Dictionary { entries, .. } => visit_map(entries, visitor),
+        }
+    }
+
+    deserialize_integer! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            amf3::Value::Null | amf3::Value::Undefined => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool f32 f64 char str string bytes byte_buf unit unit_struct newtype_struct seq
+        tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+fn visit_seq<'de, T, V>(values: Vec<T>, visitor: V) -> Result<V::Value>
+where
+    T: IntoDeserializer<'de, Error>,
+    V: Visitor<'de>,
+{
+    let mut seq = SeqDeserializer::new(values.into_iter());
+    let value = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(value)
+}
+
+fn visit_map<'de, K, T, V>(properties: Vec<(K, T)>, visitor: V) -> Result<V::Value>
+where
+    K: IntoDeserializer<'de, Error>,
+    T: IntoDeserializer<'de, Error>,
+    V: Visitor<'de>,
+{
+    let mut map = MapDeserializer::new(properties.into_iter());
+    let value = visitor.visit_map(&mut map)?;
+    map.end()?;
+    Ok(value)
+}
+
+/// An enum variant and its content, `None` for a unit variant
+struct Enum {
+    variant: String,
+    value: Option<Value>,
+}
+
+impl<'de> de::EnumAccess<'de> for Enum {
+    type Error = Error;
+    type Variant = Content;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Content)> {
+        let variant = IntoDeserializer::<Error>::into_deserializer(self.variant);
+        Ok((seed.deserialize(variant)?, Content(self.value)))
+    }
+}
+
+/// The content of an enum variant
+struct Content(Option<Value>);
+
+impl<'de> VariantAccess<'de> for Content {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.0 {
+            None | Some(Value::Null) | Some(Value::Undefined) => Ok(()),
+            Some(value) => Err(de::Error::invalid_type(value.unexpected(), &"unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        match self.0 {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Some(value) => value.deserialize_seq(visitor),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &visitor)),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.0 {
+            Some(value) => value.deserialize_map(visitor),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &visitor)),
+        }
+    }
+}
@@ -0,0 +1,733 @@
+//! AMF3 values, embedded in AMF0 script data after an `avmplus-object`
+//! marker, see [`amf0::Value::Avmplus`](crate::amf0::Value::Avmplus).
+//!
+//! References to strings, objects and traits are resolved while reading, the
+//! referenced value being copied, so a [`Value`] is a plain tree. A cyclic
+//! reference fails with [`ParseError::Amf3Reference`], and as references can
+//! make small inputs decode to huge trees, the decoded size is limited by
+//! [`MAX_SIZE`]. Writing references repeated strings and traits, but not
+//! objects.
+
+use crate::amf0::{read_f64, read_u32, read_u8, read_utf8, take};
+use crate::error::ParseError;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// AMF3 type markers
+pub mod marker {
+    pub const UNDEFINED: u8 = 0x00;
+    pub const NULL: u8 = 0x01;
+    pub const FALSE: u8 = 0x02;
+    pub const TRUE: u8 = 0x03;
+    pub const INTEGER: u8 = 0x04;
+    pub const DOUBLE: u8 = 0x05;
+    pub const STRING: u8 = 0x06;
+    pub const XML_DOCUMENT: u8 = 0x07;
+    pub const DATE: u8 = 0x08;
+    pub const ARRAY: u8 = 0x09;
+    pub const OBJECT: u8 = 0x0A;
+    pub const XML: u8 = 0x0B;
+    pub const BYTE_ARRAY: u8 = 0x0C;
+    pub const VECTOR_INT: u8 = 0x0D;
+    pub const VECTOR_UINT: u8 = 0x0E;
+    pub const VECTOR_DOUBLE: u8 = 0x0F;
+    pub const VECTOR_OBJECT: u8 = 0x10;
+    pub const DICTIONARY: u8 = 0x11;
+}
+
+/// Externalizable classes whose content is a single value, which are the
+/// only ones that can be read without knowing the class
+const EXTERNALIZABLE_CLASSES: [&str; 2] = [
+    "flex.messaging.io.ArrayCollection",
+    "flex.messaging.io.ObjectProxy",
+];
+
+/// The range of the AMF3 integer type
+pub const INTEGER_MIN: i32 = -(1 << 28);
+pub const INTEGER_MAX: i32 = (1 << 28) - 1;
+
+/// How deep values may be nested in the data read by [`Value::read`]
+pub const MAX_DEPTH: usize = 64;
+
+/// Largest size in bytes of a value read by [`Value::read`], estimated from
+/// its nodes and contents with references resolved
+pub const MAX_SIZE: usize = 64 << 20;
+
+/// Named members of an object or of the associative part of an array, in
+/// wire order
+pub type Properties = Vec<(String, Value)>;
+
+/// AMF3 value
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Value {
+    Undefined,
+    Null,
+    Boolean(bool),
+    /// a 29-bit signed integer, see [`INTEGER_MIN`] and [`INTEGER_MAX`]
+    Integer(i32),
+    Double(f64),
+    String(String),
+    XmlDocument(String),
+    /// milliseconds since 1970-01-01 00:00:00 UTC
+    Date(f64),
+    Array {
+        associative: Properties,
+        dense: Vec<Value>,
+    },
+    Object(Object),
+    /// an externalizable object of one of the classes wrapping a single
+    /// value, such as `flex.messaging.io.ArrayCollection`
+    Externalizable {
+        class_name: String,
+        value: alloc::boxed::Box<Value>,
+    },
+    Xml(String),
+    ByteArray(Vec<u8>),
+    VectorInt {
+        fixed: bool,
+        values: Vec<i32>,
+    },
+    VectorUint {
+        fixed: bool,
+        values: Vec<u32>,
+    },
+    VectorDouble {
+        fixed: bool,
+        values: Vec<f64>,
+    },
+    VectorObject {
+        fixed: bool,
+        /// class name of the items, empty for `Object`
+        type_name: String,
+        values: Vec<Value>,
+    },
+    Dictionary {
+        weak_keys: bool,
+        entries: Vec<(Value, Value)>,
+    },
+}
+
+/// An AMF3 object
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Object {
+    /// class name, empty for an anonymous object
+    pub class_name: String,
+
+    /// members declared by the class, in order
+    pub sealed: Properties,
+
+    /// members added to a dynamic object, `None` for a non-dynamic one
+    pub dynamic: Option<Properties>,
+}
+
+impl Value {
+    /// Reads one value from the front of `buf` and advances it, with empty
+    /// reference tables.
+    ///
+    /// Values nested deeper than [`MAX_DEPTH`] fail with
+    /// [`ParseError::Amf3Depth`], and values bigger than [`MAX_SIZE`] with
+    /// [`ParseError::Amf3Size`].
+    pub fn read(buf: &mut &[u8]) -> Result<Self, ParseError> {
+        Decoder::default().read(buf)
+    }
+
+    /// Appends the encoded value to `buf`, with empty reference tables.
+    pub fn write(&self, buf: &mut Vec<u8>) {
+        Encoder::default().write(buf, self)
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Integer(n) => Some((*n).into()),
+            Value::Double(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Looks up a member of an object or of the associative part of an
+    /// array by name.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        let mut properties = match self {
+            Value::Array { associative, .. } => associative.iter().chain(&[]),
+            Value::Object(object) => object
+                .sealed
+                .iter()
+                .chain(object.dynamic.as_deref().unwrap_or_default()),
+            _ => return None,
+        };
+
+        properties
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    }
+}
+
+/// The class name and sealed member names shared by objects of a class
+#[derive(Debug, Clone, PartialEq)]
+struct Traits {
+    class_name: String,
+    sealed: Vec<String>,
+    dynamic: bool,
+    externalizable: bool,
+}
+
+/// Reference tables of a read, objects being `None` while they are read
+#[derive(Default)]
+struct Decoder {
+    strings: Vec<String>,
+    /// values with their size, as charged while reading them
+    objects: Vec<Option<(Value, usize)>>,
+    traits: Vec<Traits>,
+
+    depth: usize,
+    /// estimated size of the values read so far, copies included
+    size: usize,
+}
+
+impl Decoder {
+    fn read(&mut self, buf: &mut &[u8]) -> Result<Value, ParseError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(ParseError::Amf3Depth);
+        }
+        self.charge(core::mem::size_of::<Value>())?;
+
+        self.depth += 1;
+        let value = self.read_value(buf);
+        self.depth -= 1;
+        value
+    }
+
+    /// Adds `size` bytes to the decoded size, failing past [`MAX_SIZE`].
+    fn charge(&mut self, size: usize) -> Result<(), ParseError> {
+        self.size = self.size.saturating_add(size);
+        match self.size > MAX_SIZE {
+            true => Err(ParseError::Amf3Size),
+            false => Ok(()),
+        }
+    }
+
+    fn read_value(&mut self, buf: &mut &[u8]) -> Result<Value, ParseError> {
+        Ok(match read_u8(buf)? {
+            marker::UNDEFINED => Value::Undefined,
+            marker::NULL => Value::Null,
+            marker::FALSE => Value::Boolean(false),
+            marker::TRUE => Value::Boolean(true),
+            marker::INTEGER => {
+                // sign extension of the 29 bits
+                let n = read_u29(buf)?;
+                Value::Integer(((n << 3) as i32) >> 3)
+            }
+            marker::DOUBLE => Value::Double(read_f64(buf)?),
+            marker::STRING => Value::String(self.read_string(buf)?),
+            marker::XML_DOCUMENT => self.read_object(buf, |decoder, buf, len| {
+                let s = read_utf8(buf, len as usize)?;
+                decoder.charge(s.len())?;
+                Ok(Value::XmlDocument(s))
+            })?,
+            marker::DATE => self.read_object(buf, |_, buf, _| Ok(Value::Date(read_f64(buf)?)))?,
+            marker::ARRAY => self.read_object(buf, |decoder, buf, len| {
+                let associative = decoder.read_properties(buf)?;
+                let dense = read_items(buf, len, |buf| decoder.read(buf))?;
+                Ok(Value::Array { associative, dense })
+            })?,
+            marker::OBJECT => self.read_class_object(buf)?,
+            marker::XML => self.read_object(buf, |decoder, buf, len| {
+                let s = read_utf8(buf, len as usize)?;
+                decoder.charge(s.len())?;
+                Ok(Value::Xml(s))
+            })?,
+            marker::BYTE_ARRAY => self.read_object(buf, |decoder, buf, len| {
+                let bytes = take(buf, len as usize)?;
+                decoder.charge(bytes.len())?;
+                Ok(Value::ByteArray(bytes.to_vec()))
+            })?,
+            marker::VECTOR_INT => self.read_object(buf, |decoder, buf, len| {
+                let fixed = read_u8(buf)? != 0;
+                let values = read_items(buf, len, |buf| Ok(read_u32(buf)? as i32))?;
+                decoder.charge(values.len() * 4)?;
+                Ok(Value::VectorInt { fixed, values })
+            })?,
+            marker::VECTOR_UINT => self.read_object(buf, |decoder, buf, len| {
+                let fixed = read_u8(buf)? != 0;
+                let values = read_items(buf, len, read_u32)?;
+                decoder.charge(values.len() * 4)?;
+                Ok(Value::VectorUint { fixed, values })
+            })?,
+            marker::VECTOR_DOUBLE => self.read_object(buf, |decoder, buf, len| {
+                let fixed = read_u8(buf)? != 0;
+                let values = read_items(buf, len, read_f64)?;
+                decoder.charge(values.len() * 8)?;
+                Ok(Value::VectorDouble { fixed, values })
+            })?,
+            marker::VECTOR_OBJECT => self.read_object(buf, |decoder, buf, len| {
+                let fixed = read_u8(buf)? != 0;
+                let type_name = decoder.read_string(buf)?;
+                let values = read_items(buf, len, |buf| decoder.read(buf))?;
+                Ok(Value::VectorObject {
+                    fixed,
+                    type_name,
+                    values,
+                })
+            })?,
+            marker::DICTIONARY => self.read_object(buf, |decoder, buf, len| {
+                let weak_keys = read_u8(buf)? != 0;
+                let entries =
+                    read_items(buf, len, |buf| Ok((decoder.read(buf)?, decoder.read(buf)?)))?;
+                Ok(Value::Dictionary { weak_keys, entries })
+            })?,
+            n => return Err(ParseError::Amf3Marker(n)),
+        })
+    }
+
+    /// Reads a string, or a reference to a previous one.
+    fn read_string(&mut self, buf: &mut &[u8]) -> Result<String, ParseError> {
+        let n = read_u29(buf)?;
+        if n & 1 == 0 {
+            let index = n >> 1;
+            let len = self
+                .strings
+                .get(index as usize)
+                .ok_or(ParseError::Amf3Reference(index))?
+                .len();
+            self.charge(len)?;
+            return Ok(self.strings[index as usize].clone());
+        }
+
+        let s = read_utf8(buf, (n >> 1) as usize)?;
+        self.charge(s.len())?;
+        // the empty string is never referenced
+        if !s.is_empty() {
+            self.strings.push(s.clone());
+        }
+        Ok(s)
+    }
+
+    /// Reads a value of the object table, or a reference to a previous one,
+    /// the rest of the U29 header being passed to `read_value`.
+    fn read_object(
+        &mut self,
+        buf: &mut &[u8],
+        read_value: impl FnOnce(&mut Self, &mut &[u8], u32) -> Result<Value, ParseError>,
+    ) -> Result<Value, ParseError> {
+        let n = read_u29(buf)?;
+        if n & 1 == 0 {
+            return self.reference(n >> 1);
+        }
+
+        let index = self.objects.len();
+        self.objects.push(None);
+        let start = self.size;
+        let value = read_value(self, buf, n >> 1)?;
+        self.objects[index] = Some((value.clone(), self.size - start));
+        Ok(value)
+    }
+
+    /// Copies a value of the object table, charging its size again.
+    fn reference(&mut self, index: u32) -> Result<Value, ParseError> {
+        let size = match self.objects.get(index as usize) {
+            Some(Some((_, size))) => *size,
+            _ => return Err(ParseError::Amf3Reference(index)),
+        };
+        self.charge(size)?;
+
+        match &self.objects[index as usize] {
+            Some((value, _)) => Ok(value.clone()),
+            None => Err(ParseError::Amf3Reference(index)),
+        }
+    }
+
+    fn read_class_object(&mut self, buf: &mut &[u8]) -> Result<Value, ParseError> {
+        self.read_object(buf, |decoder, buf, n| {
+            let traits = if n & 1 == 0 {
+                let index = n >> 1;
+                let traits = decoder
+                    .traits
+                    .get(index as usize)
+                    .cloned()
+                    .ok_or(ParseError::Amf3Reference(index))?;
+                let names = traits.sealed.iter().map(String::len).sum::<usize>();
+                decoder.charge(traits.class_name.len() + names)?;
+                traits
+            } else {
+                let traits = Traits {
+                    externalizable: n & 0b10 != 0,
+                    dynamic: n & 0b100 != 0,
+                    class_name: decoder.read_string(buf)?,
+                    sealed: read_items(buf, n >> 3, |buf| decoder.read_string(buf))?,
+                };
+                decoder.traits.push(traits.clone());
+                traits
+            };
+
+            if traits.externalizable {
+                if !EXTERNALIZABLE_CLASSES.contains(&traits.class_name.as_str()) {
+                    return Err(ParseError::Amf3Externalizable);
+                }
+                return Ok(Value::Externalizable {
+                    value: alloc::boxed::Box::new(decoder.read(buf)?),
+                    class_name: traits.class_name,
+                });
+            }
+
+            let mut sealed = Vec::with_capacity(traits.sealed.len());
+            for name in traits.sealed {
+                sealed.push((name, decoder.read(buf)?));
+            }
+            let dynamic = match traits.dynamic {
+                true => Some(decoder.read_properties(buf)?),
+                false => None,
+            };
+
+            Ok(Value::Object(Object {
+                class_name: traits.class_name,
+                sealed,
+                dynamic,
+            }))
+        })
+    }
+
+    /// Reads name and value pairs up to an empty name.
+    fn read_properties(&mut self, buf: &mut &[u8]) -> Result<Properties, ParseError> {
+        let mut properties = vec![];
+        loop {
+            let name = self.read_string(buf)?;
+            if name.is_empty() {
+                return Ok(properties);
+            }
+            properties.push((name, self.read(buf)?));
+        }
+    }
+}
+
+/// Reads `len` items, without trusting `len` for the allocation.
+fn read_items<T>(
+    buf: &mut &[u8],
+    len: u32,
+    mut read_item: impl FnMut(&mut &[u8]) -> Result<T, ParseError>,
+) -> Result<Vec<T>, ParseError> {
+    let mut items = Vec::with_capacity((len as usize).min(buf.len()));
+    for _ in 0..len {
+        items.push(read_item(buf)?);
+    }
+    Ok(items)
+}
+
+/// Reads a variable length unsigned 29-bit integer: 7 bits in each of the
+/// first 3 bytes while their high bit is set, then 8 bits.
+fn read_u29(buf: &mut &[u8]) -> Result<u32, ParseError> {
+    let mut n = 0;
+    for _ in 0..3 {
+        let b = read_u8(buf)?;
+        n = (n << 7) | u32::from(b & 0x7f);
+        if b & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Ok((n << 8) | u32::from(read_u8(buf)?))
+}
+
+fn write_u29(buf: &mut Vec<u8>, n: u32) {
+    let n = n & 0x1fff_ffff;
+    match n {
+        0..=0x7f => buf.push(n as u8),
+        0x80..=0x3fff => buf.extend_from_slice(&[(n >> 7) as u8 | 0x80, n as u8 & 0x7f]),
+        0x4000..=0x1f_ffff => buf.extend_from_slice(&[
+            (n >> 14) as u8 | 0x80,
+            (n >> 7) as u8 | 0x80,
+            n as u8 & 0x7f,
+        ]),
+        _ => buf.extend_from_slice(&[
+            (n >> 22) as u8 | 0x80,
+            (n >> 15) as u8 | 0x80,
+            (n >> 8) as u8 | 0x80,
+            n as u8,
+        ]),
+    }
+}
+
+/// Reference tables of a write
+#[derive(Default)]
+struct Encoder {
+    strings: BTreeMap<String, u32>,
+    traits: Vec<Traits>,
+}
+
+impl Encoder {
+    fn write(&mut self, buf: &mut Vec<u8>, value: &Value) {
+        match value {
+            Value::Undefined => buf.push(marker::UNDEFINED),
+            Value::Null => buf.push(marker::NULL),
+            Value::Boolean(false) => buf.push(marker::FALSE),
+            Value::Boolean(true) => buf.push(marker::TRUE),
+            Value::Integer(n) if (INTEGER_MIN..=INTEGER_MAX).contains(n) => {
+                buf.push(marker::INTEGER);
+                write_u29(buf, *n as u32);
+            }
+            Value::Integer(n) => {
+                buf.push(marker::DOUBLE);
+                buf.extend_from_slice(&f64::from(*n).to_be_bytes());
+            }
+            Value::Double(n) => {
+                buf.push(marker::DOUBLE);
+                buf.extend_from_slice(&n.to_be_bytes());
+            }
+            Value::String(s) => {
+                buf.push(marker::STRING);
+                self.write_string(buf, s);
+            }
+            Value::XmlDocument(s) => {
+                buf.push(marker::XML_DOCUMENT);
+                write_inline(buf, s.len());
+                buf.extend_from_slice(s.as_bytes());
+            }
+            Value::Date(millis) => {
+                buf.push(marker::DATE);
+                write_inline(buf, 0);
+                buf.extend_from_slice(&millis.to_be_bytes());
+            }
+            Value::Array { associative, dense } => {
+                buf.push(marker::ARRAY);
+                write_inline(buf, dense.len());
+                self.write_properties(buf, associative);
+                for value in dense {
+                    self.write(buf, value);
+                }
+            }
+            Value::Object(object) => {
+                buf.push(marker::OBJECT);
+                let traits = Traits {
+                    class_name: object.class_name.clone(),
+                    sealed: object.sealed.iter().map(|(name, _)| name.clone()).collect(),
+                    dynamic: object.dynamic.is_some(),
+                    externalizable: false,
+                };
+                self.write_traits(buf, traits);
+                for (_, value) in &object.sealed {
+                    self.write(buf, value);
+                }
+                if let Some(dynamic) = &object.dynamic {
+                    self.write_properties(buf, dynamic);
+                }
+            }
+            Value::Externalizable { class_name, value } => {
+                buf.push(marker::OBJECT);
+                let traits = Traits {
+                    class_name: class_name.clone(),
+                    sealed: vec![],
+                    dynamic: false,
+                    externalizable: true,
+                };
+                self.write_traits(buf, traits);
+                self.write(buf, value);
+            }
+            Value::Xml(s) => {
+                buf.push(marker::XML);
+                write_inline(buf, s.len());
+                buf.extend_from_slice(s.as_bytes());
+            }
+            Value::ByteArray(bytes) => {
+                buf.push(marker::BYTE_ARRAY);
+                write_inline(buf, bytes.len());
+                buf.extend_from_slice(bytes);
+            }
+            Value::VectorInt { fixed, values } => {
+                buf.push(marker::VECTOR_INT);
+                write_inline(buf, values.len());
+                buf.push(*fixed as u8);
+                for n in values {
+                    buf.extend_from_slice(&n.to_be_bytes());
+                }
+            }
+            Value::VectorUint { fixed, values } => {
+                buf.push(marker::VECTOR_UINT);
+                write_inline(buf, values.len());
+                buf.push(*fixed as u8);
+                for n in values {
+                    buf.extend_from_slice(&n.to_be_bytes());
+                }
+            }
+            Value::VectorDouble { fixed, values } => {
+                buf.push(marker::VECTOR_DOUBLE);
+                write_inline(buf, values.len());
+                buf.push(*fixed as u8);
+                for n in values {
+                    buf.extend_from_slice(&n.to_be_bytes());
+                }
+            }
+            Value::VectorObject {
+                fixed,
+                type_name,
+                values,
+            } => {
+                buf.push(marker::VECTOR_OBJECT);
+                write_inline(buf, values.len());
+                buf.push(*fixed as u8);
+                self.write_string(buf, type_name);
+                for value in values {
+                    self.write(buf, value);
+                }
+            }
+            Value::Dictionary { weak_keys, entries } => {
+                buf.push(marker::DICTIONARY);
+                write_inline(buf, entries.len());
+                buf.push(*weak_keys as u8);
+                for (key, value) in entries {
+                    self.write(buf, key);
+                    self.write(buf, value);
+                }
+            }
+        }
+    }
+
+    /// Writes a string, or a reference to a previous one.
+    fn write_string(&mut self, buf: &mut Vec<u8>, s: &str) {
+        if let Some(&index) = self.strings.get(s) {
+            write_u29(buf, index << 1);
+            return;
+        }
+
+        if !s.is_empty() {
+            let index = self.strings.len() as u32;
+            self.strings.insert(s.into(), index);
+        }
+        write_inline(buf, s.len());
+        buf.extend_from_slice(s.as_bytes());
+    }
+
+    /// Writes the traits of an object, or a reference to previous ones.
+    fn write_traits(&mut self, buf: &mut Vec<u8>, traits: Traits) {
+        // the object itself is never a reference
+        if let Some(index) = self.traits.iter().position(|t| *t == traits) {
+            write_u29(buf, (index as u32) << 2 | 0b01);
+            return;
+        }
+
+        let flags = 0b011 | (traits.externalizable as u32) << 2 | (traits.dynamic as u32) << 3;
+        write_u29(buf, (traits.sealed.len() as u32) << 4 | flags);
+        self.write_string(buf, &traits.class_name);
+        for name in &traits.sealed {
+            self.write_string(buf, name);
+        }
+        self.traits.push(traits);
+    }
+
+    /// Writes name and value pairs followed by an empty name.
+    fn write_properties(&mut self, buf: &mut Vec<u8>, properties: &[(String, Value)]) {
+        for (name, value) in properties {
+            self.write_string(buf, name);
+            self.write(buf, value);
+        }
+        self.write_string(buf, "");
+    }
+}
+
+/// Writes the U29 header of an inline value of length `len`.
+fn write_inline(buf: &mut Vec<u8>, len: usize) {
+    write_u29(buf, (len as u32) << 1 | 1);
+}
+
+#[test]
+fn amf3_round_trip() {
+    use alloc::borrow::ToOwned;
+
+    let point = |x| {
+        Value::Object(Object {
+            class_name: "Point".to_owned(),
+            sealed: vec![
+                ("x".to_owned(), Value::Integer(x)),
+                ("y".to_owned(), Value::Double(0.5)),
+            ],
+            dynamic: None,
+        })
+    };
+    let value = Value::Array {
+        associative: vec![("name".to_owned(), Value::String("Point".to_owned()))],
+        dense: vec![
+            // the second point only references the "Point" string and traits
+            point(INTEGER_MIN),
+            point(INTEGER_MAX),
+            Value::Object(Object {
+                dynamic: Some(vec![("name".to_owned(), Value::Null)]),
+                ..Object::default()
+            }),
+            Value::ByteArray(vec![1, 2, 3]),
+            Value::VectorInt {
+                fixed: true,
+                values: vec![-1, 300],
+            },
+            Value::Dictionary {
+                weak_keys: false,
+                entries: vec![(Value::Integer(1), Value::Date(1.6e12))],
+            },
+        ],
+    };
+
+    let mut buf = vec![];
+    value.write(&mut buf);
+    assert_eq!(Ok(value), Value::read(&mut &buf[..]));
+
+    let mut buf = vec![];
+    Value::Integer(INTEGER_MAX + 1).write(&mut buf);
+    assert_eq!(
+        Ok(Value::Double((INTEGER_MAX + 1).into())),
+        Value::read(&mut &buf[..])
+    );
+}
+
+#[test]
+fn amf3_references() {
+    use alloc::borrow::ToOwned;
+
+    // an array of an anonymous dynamic object {x: 5} and of a reference to it
+    let buf = [9, 5, 1, 10, 11, 1, 3, b'x', 4, 5, 1, 10, 2];
+    let object = Value::Object(Object {
+        dynamic: Some(vec![("x".to_owned(), Value::Integer(5))]),
+        ..Object::default()
+    });
+    assert_eq!(
+        Ok(Value::Array {
+            associative: vec![],
+            dense: vec![object.clone(), object],
+        }),
+        Value::read(&mut &buf[..])
+    );
+
+    // an object referencing itself
+    let buf = [10, 11, 1, 3, b'x', 10, 0];
+    assert_eq!(
+        Err(ParseError::Amf3Reference(0)),
+        Value::read(&mut &buf[..])
+    );
+}
+
+#[test]
+fn amf3_limits() {
+    // arrays of two references to the previous array, doubling the size
+    let mut buf = vec![9, 81, 1, 9, 3, 1, 1];
+    for index in 1..40 {
+        buf.extend_from_slice(&[9, 5, 1, 9, index << 1, 9, index << 1]);
+    }
+    assert_eq!(Err(ParseError::Amf3Size), Value::read(&mut &buf[..]));
+
+    let mut buf = [9, 3, 1].repeat(MAX_DEPTH + 1);
+    buf.push(1);
+    assert_eq!(Err(ParseError::Amf3Depth), Value::read(&mut &buf[..]));
+}
@@ -0,0 +1,535 @@
+//! The async backends, generated by [`async_backend!`] for the I/O traits of
+//! each runtime.
+#![cfg(any(feature = "io-tokio", feature = "io-futures"))]
+
+/// Defines the writers, the reader and `inject_metadata` of an async backend
+/// in the invoking module.
+///
+/// The module brings in scope the `AsyncRead`, `AsyncWrite`, `AsyncSeek`,
+/// `AsyncSeekExt` and `AsyncWriteExt` traits of its runtime, and an
+/// `async fn read_buf(reader, buf) -> std::io::Result<usize>` reading once
+/// from an `AsyncRead + Unpin` source into `buf`.
+macro_rules! async_backend {
+    () => {
+        use crate::amf0::Value;
+        use crate::interleave::queue::{InterleaverCore, Media, Packet};
+        use crate::reader::ReaderCore;
+        use crate::recovery::RecoveryState;
+        use crate::tag::is_metadata;
+        use crate::writer::{Injection, WriterCore};
+        use crate::{
+            AudioDataHeader, Decryptor, Error, Header, Interleaving, MetaData, ParseMode, Recovery,
+            Result, ScriptData, ScriptMessage, SkippedRange, Tag, TagData, TagHeader,
+            VideoDataHeader, Warning,
+        };
+        use std::io::SeekFrom;
+
+        pub struct FlvWriter<W> {
+            writer: W,
+            core: WriterCore,
+        }
+
+        impl<W: AsyncWrite + Unpin> FlvWriter<W> {
+            pub fn new(writer: W) -> Self {
+                Self {
+                    writer,
+                    core: WriterCore::new(),
+                }
+            }
+
+            pub fn into_inner(self) -> W {
+                self.writer
+            }
+
+            /// Writes the file header, padded with zeros up to `header.data_offset`.
+            ///
+            /// The padding is limited to [`Header::MAX_EXTENSION_SIZE`], use
+            /// [`FlvWriter::write_header_with_extension`] to write more.
+            pub async fn write_header(&mut self, header: Header) -> Result<u64> {
+                let written = self.core.write_header(header)?;
+                self.write_buffer().await?;
+                Ok(written)
+            }
+
+            /// Writes the file header followed by `extension`, `header.data_offset`
+            /// is replaced to point right after it.
+            pub async fn write_header_with_extension(
+                &mut self,
+                header: Header,
+                extension: &[u8],
+            ) -> Result<u64> {
+                let written = self.core.write_header_with_extension(header, extension)?;
+                self.write_buffer().await?;
+                Ok(written)
+            }
+
+            pub async fn write_metadata(&mut self, metadata: &MetaData) -> Result<u64> {
+                let written = self.core.write_metadata(metadata)?;
+                self.write_buffer().await?;
+                Ok(written)
+            }
+
+            /// Writes a script tag carrying `message`, such as an onCuePoint whose
+            /// timestamp matches the cue point time.
+            pub async fn write_script_message(
+                &mut self,
+                timestamp: i32,
+                message: &ScriptMessage,
+            ) -> Result<u64> {
+                let written = self.core.write_script_message(timestamp, message)?;
+                self.write_buffer().await?;
+                Ok(written)
+            }
+
+            /// Writes a script tag made of the `name` string followed by `values`,
+            /// such as a custom data message.
+            pub async fn write_script_tag(
+                &mut self,
+                timestamp: i32,
+                name: &str,
+                values: &[Value],
+            ) -> Result<u64> {
+                let written = self.core.write_script_tag(timestamp, name, values)?;
+                self.write_buffer().await?;
+                Ok(written)
+            }
+
+            /// Writes `data` as the whole body of a tag of any type, `Reserved`
+            /// included, to pass existing tags through unchanged.
+            ///
+            /// The data size of `tag_header` is replaced by the length of `data`, a
+            /// tag type or reserved bits that don't fit in a tag header fail the write.
+            /// Unfiltered audio and video tags with a zero stream id are accounted
+            /// for in the metadata of [`FlvWriter::finalize`], as with
+            /// [`FlvWriter::write_whole_tag`].
+            pub async fn write_raw_tag(
+                &mut self,
+                tag_header: TagHeader,
+                data: &[u8],
+            ) -> Result<u64> {
+                let written = self.core.write_raw_tag(tag_header, data)?;
+                self.write_buffer().await?;
+                Ok(written)
+            }
+
+            /// Writes a placeholder onMetaData tag to be filled in by
+            /// [`FlvWriter::finalize`] once all tags are written.
+            ///
+            /// The reserved tag has room for every field except `keyframes`.
+            pub async fn reserve_metadata(&mut self) -> Result<u64> {
+                let written = self.core.reserve_metadata()?;
+                self.write_buffer().await?;
+                Ok(written)
+            }
+
+            pub async fn write_video_tag(
+                &mut self,
+                timestamp: i32,
+                header: VideoDataHeader,
+                data: &[u8],
+            ) -> Result<u64> {
+                let written = self.core.write_video_tag(timestamp, header, data)?;
+                self.write_buffer().await?;
+                Ok(written)
+            }
+
+            pub async fn write_audio_tag(
+                &mut self,
+                timestamp: i32,
+                header: AudioDataHeader,
+                data: &[u8],
+            ) -> Result<u64> {
+                let written = self.core.write_audio_tag(timestamp, header, data)?;
+                self.write_buffer().await?;
+                Ok(written)
+            }
+
+            /// Writes a tag as read, filtered tags are passed through untouched.
+            async fn write_whole_tag<D: AsRef<[u8]>>(&mut self, tag: &Tag<D>) -> Result<u64> {
+                let written = self.core.write_whole_tag(tag)?;
+                self.write_buffer().await?;
+                Ok(written)
+            }
+
+            async fn write_buffer(&mut self) -> Result<()> {
+                let result = self.writer.write_all(&self.core.buffer).await;
+                self.core.buffer.clear();
+                Ok(result?)
+            }
+        }
+
+        impl<W: AsyncWrite + AsyncSeek + Unpin> FlvWriter<W> {
+            /// Patches the header flags and the metadata reserved by
+            /// [`FlvWriter::reserve_metadata`] to match the tags actually written,
+            /// then returns to the end of the output.
+            pub async fn finalize(&mut self) -> Result<MetaData> {
+                let end = self.writer.stream_position().await?;
+                let (metadata, patches) = self.core.finalize(end);
+
+                for (offset, bytes) in patches {
+                    self.writer.seek(SeekFrom::Start(offset)).await?;
+                    self.writer.write_all(&bytes).await?;
+                }
+
+                self.writer.seek(SeekFrom::Start(end)).await?;
+                self.writer.flush().await?;
+
+                Ok(metadata)
+            }
+        }
+
+        /// Buffers audio and video tags coming from separate producers and writes
+        /// them with non-decreasing timestamps, within the latency window of
+        /// [`Interleaving`].
+        ///
+        /// Each track must be pushed in timestamp order. The header and metadata
+        /// are written directly with [`InterleavingWriter::get_mut`] before the
+        /// first tag.
+        pub struct InterleavingWriter<W> {
+            writer: FlvWriter<W>,
+            core: InterleaverCore,
+        }
+
+        impl<W: AsyncWrite + Unpin> InterleavingWriter<W> {
+            pub fn new(writer: FlvWriter<W>, options: Interleaving) -> Self {
+                Self {
+                    writer,
+                    core: InterleaverCore::new(options),
+                }
+            }
+
+            pub fn get_ref(&self) -> &FlvWriter<W> {
+                &self.writer
+            }
+
+            pub fn get_mut(&mut self) -> &mut FlvWriter<W> {
+                &mut self.writer
+            }
+
+            /// Writes the buffered tags, then returns the inner writer.
+            pub async fn into_inner(mut self) -> Result<FlvWriter<W>> {
+                self.flush().await?;
+                Ok(self.writer)
+            }
+
+            /// Tags waiting for the other track
+            pub fn buffered(&self) -> usize {
+                self.core.len()
+            }
+
+            /// Tags older than the last one written when they were pushed
+            pub fn late_tags(&self) -> u64 {
+                self.core.late
+            }
+
+            /// Queues an audio tag, then writes the tags that are ready.
+            pub async fn write_audio_tag(
+                &mut self,
+                timestamp: i32,
+                header: AudioDataHeader,
+                data: &[u8],
+            ) -> Result<u64> {
+                self.core.push(Packet {
+                    timestamp,
+                    media: Media::Audio(header),
+                    data: data.to_vec(),
+                })?;
+                self.write_ready().await
+            }
+
+            /// Queues a video tag, then writes the tags that are ready.
+            pub async fn write_video_tag(
+                &mut self,
+                timestamp: i32,
+                header: VideoDataHeader,
+                data: &[u8],
+            ) -> Result<u64> {
+                self.core.push(Packet {
+                    timestamp,
+                    media: Media::Video(header),
+                    data: data.to_vec(),
+                })?;
+                self.write_ready().await
+            }
+
+            /// Writes every buffered tag, for the end of the stream.
+            pub async fn flush(&mut self) -> Result<u64> {
+                let mut written = 0;
+                while let Some(packet) = self.core.pop_any() {
+                    written += self.write_packet(packet).await?;
+                }
+                Ok(written)
+            }
+
+            async fn write_ready(&mut self) -> Result<u64> {
+                let mut written = 0;
+                while let Some(packet) = self.core.pop() {
+                    written += self.write_packet(packet).await?;
+                }
+                Ok(written)
+            }
+
+            async fn write_packet(&mut self, packet: Packet) -> Result<u64> {
+                let Packet {
+                    timestamp,
+                    media,
+                    data,
+                } = packet;
+
+                match media {
+                    Media::Audio(header) => {
+                        self.writer.write_audio_tag(timestamp, header, &data).await
+                    }
+                    Media::Video(header) => {
+                        self.writer.write_video_tag(timestamp, header, &data).await
+                    }
+                }
+            }
+        }
+
+        pub struct FlvReader<R> {
+            reader: R,
+            core: ReaderCore,
+        }
+
+        impl<R: AsyncRead + Unpin> FlvReader<R> {
+            pub fn new(reader: R) -> Self {
+                Self {
+                    reader,
+                    core: ReaderCore::new(),
+                }
+            }
+
+            /// Sets how unknown field values and anomalies such as non-zero stream
+            /// ids are handled, [`ParseMode::Strict`] by default.
+            pub fn mode(mut self, mode: ParseMode) -> Self {
+                self.core.validator.mode = mode;
+                self
+            }
+
+            /// Shorthand for [`ParseMode::Strict`] or [`ParseMode::Lenient`].
+            pub fn strict(self, strict: bool) -> Self {
+                self.mode(if strict {
+                    ParseMode::Strict
+                } else {
+                    ParseMode::Lenient
+                })
+            }
+
+            /// Decrypts filtered tags in [`FlvReader::read_tag`] with `decryptor`.
+            ///
+            /// Without a decryptor, filtered tags are returned untouched with
+            /// [`TagHeader::filter`] set.
+            pub fn with_decryptor<D: Decryptor + Send + 'static>(mut self, decryptor: D) -> Self {
+                self.core.decryptor = Some(Box::new(decryptor));
+                self
+            }
+
+            /// Enables the recovery mode.
+            ///
+            /// When a tag header or a PreviousTagSize doesn't check out,
+            /// [`FlvReader::read_tag`] scans forward for the next tag instead of
+            /// failing: a known tag type, a zero stream id and a following
+            /// PreviousTagSize matching the tag. A tag larger than `max_data_size` or
+            /// going back in time from the last tag is only taken if no other tag
+            /// starts within it. The bytes passed over are reported by
+            /// [`FlvReader::skipped_ranges`].
+            pub fn with_recovery(mut self, options: Recovery) -> Self {
+                self.core.recovery = Some(RecoveryState::new(options));
+                self
+            }
+
+            /// Anomalies found so far in lenient mode, up to [`Warning::MAX_KEPT`]
+            /// of them until they are taken
+            pub fn warnings(&self) -> &[Warning] {
+                &self.core.validator.warnings
+            }
+
+            /// Anomalies found in lenient mode that weren't kept as warnings, the
+            /// list being full
+            pub fn dropped_warnings(&self) -> u64 {
+                self.core.validator.dropped_warnings
+            }
+
+            /// Removes the anomalies found so far, to report them as the reading
+            /// goes.
+            pub fn take_warnings(&mut self) -> Vec<Warning> {
+                std::mem::take(&mut self.core.validator.warnings)
+            }
+
+            /// Byte ranges skipped in recovery mode so far
+            pub fn skipped_ranges(&self) -> &[SkippedRange] {
+                self.core.skipped_ranges()
+            }
+
+            /// Offset of the next byte to be read from the start of the file
+            pub fn position(&self) -> u64 {
+                self.core.position()
+            }
+
+            /// Reads the file header and the extra header bytes up to
+            /// `data_offset`, the reader must be at the start of the file.
+            ///
+            /// Nothing is ever seeked while reading, so any forward-only source such
+            /// as stdin, a pipe or a socket can be read.
+            ///
+            /// The header is read at the current position, call [`FlvReader::rewind`]
+            /// first to read it again.
+            pub async fn read_header(&mut self) -> Result<Header> {
+                self.core.begin_header();
+                Ok(drive!(self.read_header().await))
+            }
+
+            /// Header bytes between [`Header::SIZE`] and `data_offset` of the last
+            /// header read, empty for a standard header.
+            ///
+            /// Only the first [`Header::MAX_EXTENSION_SIZE`] bytes are kept.
+            pub fn header_extension(&self) -> &[u8] {
+                self.core.header_extension()
+            }
+
+            /// Reads a PreviousTagSize field.
+            ///
+            /// In recovery mode, a value that doesn't match the last tag is left in
+            /// the stream to be skipped by the following [`FlvReader::read_tag`].
+            pub async fn read_previous_tag_size(&mut self) -> Result<u32> {
+                Ok(drive!(self.read_previous_tag_size().await))
+            }
+
+            pub async fn read_tag_header(&mut self) -> Result<TagHeader> {
+                Ok(drive!(self.read_tag_header().await))
+            }
+
+            /// Reads a whole tag, or returns `None` at the end of the file.
+            pub async fn read_tag(&mut self) -> Result<Option<Tag<Vec<u8>>>> {
+                Ok(drive!(self.read_tag().await))
+            }
+
+            /// Reads a whole tag like [`FlvReader::read_tag`], its payload is split
+            /// off the read buffer and shares it with the neighbouring tags, so
+            /// cloning the payload doesn't copy it.
+            #[cfg(feature = "bytes")]
+            pub async fn read_tag_bytes(&mut self) -> Result<Option<Tag<bytes::Bytes>>> {
+                Ok(drive!(self.read_tag_bytes().await))
+            }
+
+            /// Skips the remaining `size` bytes of a tag body by discarding them.
+            pub async fn skip_tag_data(&mut self, size: u64) -> Result<()> {
+                self.core.begin_skip(size);
+                drive!(self.skip_tag_data().await);
+                Ok(())
+            }
+
+            pub async fn read_video_data_header(&mut self) -> Result<VideoDataHeader> {
+                Ok(drive!(self.read_video_data_header().await))
+            }
+
+            pub async fn read_audio_data_header(&mut self) -> Result<AudioDataHeader> {
+                Ok(drive!(self.read_audio_data_header().await))
+            }
+
+            async fn fill(&mut self) -> Result<()> {
+                let read = read_buf(&mut self.reader, self.core.spare()).await?;
+                self.core.filled(read);
+
+                Ok(())
+            }
+        }
+
+        impl<R: AsyncRead + AsyncSeek + Unpin> FlvReader<R> {
+            /// Seeks back to the start of the file.
+            pub async fn rewind(&mut self) -> Result<()> {
+                self.seek(0).await
+            }
+
+            /// Seeks to `offset` from the start of the file, for example to a
+            /// keyframe position from the metadata.
+            pub async fn seek(&mut self, offset: u64) -> Result<()> {
+                self.reader.seek(SeekFrom::Start(offset)).await?;
+                self.core.seek(offset);
+
+                Ok(())
+            }
+
+            /// Reads the onMetaData script tag that precedes the audio and video tags,
+            /// leaving the reader right after it.
+            pub async fn read_metadata(&mut self) -> Result<MetaData> {
+                self.rewind().await?;
+                self.read_header().await?;
+                self.read_previous_tag_size().await?;
+
+                while let Some(tag) = self.read_tag().await? {
+                    match tag.data {
+                        TagData::ScriptData(ScriptData { data }) => {
+                            if let Ok(metadata) = MetaData::decode(&data) {
+                                return Ok(metadata);
+                            }
+                        }
+                        _ => break,
+                    }
+                    self.read_previous_tag_size().await?;
+                }
+
+                Err(Error::MetaDataNotFound)
+            }
+        }
+
+        /// Copies the FLV file in `reader` to `writer` with an onMetaData tag
+        /// computed from the whole stream placed first, see
+        /// [`io::inject_metadata`](crate::io::inject_metadata).
+        pub async fn inject_metadata<R, W>(reader: R, writer: W) -> Result<MetaData>
+        where
+            R: AsyncRead + AsyncSeek + Unpin,
+            W: AsyncWrite + Unpin,
+        {
+            let mut reader = FlvReader::new(reader);
+            let mut injection = Injection::default();
+
+            let header = reader.read_header().await?;
+            loop {
+                let tag = match reader.read_previous_tag_size().await {
+                    Ok(_) => reader.read_tag().await,
+                    Err(e) => Err(e),
+                };
+                let tag = match tag {
+                    Ok(Some(tag)) => tag,
+                    Ok(None) => break,
+                    Err(e) if e.is_eof() || e.is_truncated() => break,
+                    Err(e) => return Err(e),
+                };
+                injection.scan(&tag);
+            }
+            let metadata = injection.metadata(&header);
+
+            // second pass, the header extension is kept
+            reader.rewind().await?;
+            reader.read_header().await?;
+
+            let mut writer = FlvWriter::new(writer);
+            writer
+                .write_header_with_extension(
+                    Header {
+                        audio_flag: metadata.has_audio,
+                        video_flag: metadata.has_video,
+                        ..header
+                    },
+                    reader.header_extension(),
+                )
+                .await?;
+            writer.write_metadata(&metadata).await?;
+
+            for _ in 0..injection.tag_count {
+                reader.read_previous_tag_size().await?;
+                match reader.read_tag().await? {
+                    Some(tag) if !is_metadata(&tag) => writer.write_whole_tag(&tag).await?,
+                    Some(_) => continue,
+                    None => break,
+                };
+            }
+
+            writer.writer.flush().await?;
+
+            Ok(metadata)
+        }
+    };
+}
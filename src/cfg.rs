@@ -1,3 +1,35 @@
+macro_rules! cfg_alloc {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "alloc")]
+            #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+            $item
+        )*
+    }
+}
+
+macro_rules! cfg_mmap {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "mmap")]
+            #[cfg_attr(docsrs, doc(cfg(feature = "mmap")))]
+            $item
+        )*
+    }
+}
+
+/// Only used by the `amf0` module, which needs `alloc`
+#[cfg(feature = "alloc")]
+macro_rules! cfg_serde {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "serde")]
+            #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+            $item
+        )*
+    }
+}
+
 macro_rules! cfg_io_std {
     ($($item:item)*) => {
         $(
@@ -17,3 +49,32 @@ macro_rules! cfg_io_tokio {
         )*
     }
 }
+
+macro_rules! cfg_io_futures {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "io-futures")]
+            #[cfg_attr(docsrs, doc(cfg(feature = "io-futures")))]
+            $item
+        )*
+    }
+}
+
+/// Runs a step of a `ReaderCore` until it completes, filling the lookahead
+/// buffer from the source of the reader in between. Errors are returned with
+/// their location.
+#[cfg(any(feature = "io-std", feature = "io-tokio", feature = "io-futures"))]
+macro_rules! drive {
+    ($self:ident.$step:ident($($arg:expr),*) $(.$await:ident)?) => {
+        loop {
+            match $self.core.$step($($arg),*) {
+                Ok(Some(value)) => break value,
+                Ok(None) => {}
+                Err(e) => return Err($self.core.locate(e)),
+            }
+            if let Err(e) = $self.fill()$(.$await)? {
+                return Err($self.core.locate(e));
+            }
+        }
+    };
+}
@@ -0,0 +1,280 @@
+//! Minimal parsers for codec configuration records carried in sequence
+//! header tags, only as far as metadata needs them.
+
+use alloc::vec::Vec;
+
+/// AVCPacketType of an AVC sequence header
+pub(crate) const AVC_SEQUENCE_HEADER: u8 = 0;
+
+/// AACPacketType of an AAC sequence header
+pub(crate) const AAC_SEQUENCE_HEADER: u8 = 0;
+
+/// Picture width and height of the first SPS in an AVCDecoderConfigurationRecord
+pub(crate) fn avc_dimensions(record: &[u8]) -> Option<(u32, u32)> {
+    // configurationVersion, AVCProfileIndication, profile_compatibility,
+    // AVCLevelIndication, lengthSizeMinusOne, numOfSequenceParameterSets
+    if record.len() < 8 || record[5] & 0x1f == 0 {
+        return None;
+    }
+
+    let sps_len = u16::from_be_bytes([record[6], record[7]]) as usize;
+    let sps = record.get(8..8 + sps_len)?;
+
+    sps_dimensions(sps)
+}
+
+/// Picture width and height of an H.264 sequence parameter set NAL unit
+pub(crate) fn sps_dimensions(nal: &[u8]) -> Option<(u32, u32)> {
+    if nal.first()? & 0x1f != 7 {
+        return None;
+    }
+
+    let rbsp = remove_emulation_prevention(&nal[1..]);
+    let mut r = BitReader::new(&rbsp);
+
+    let profile_idc = r.read_bits(8)?;
+    r.skip(16)?; // constraint flags, level_idc
+    r.read_ue_max(31)?; // seq_parameter_set_id
+
+    let mut chroma_format_idc = 1;
+    let mut separate_colour_plane = false;
+    if let 100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135 = profile_idc {
+        chroma_format_idc = r.read_ue_max(3)?;
+        if chroma_format_idc == 3 {
+            separate_colour_plane = r.read_bit()?;
+        }
+        r.read_ue_max(6)?; // bit_depth_luma_minus8
+        r.read_ue_max(6)?; // bit_depth_chroma_minus8
+        r.skip(1)?; // qpprime_y_zero_transform_bypass_flag
+        if r.read_bit()? {
+            let count = if chroma_format_idc == 3 { 12 } else { 8 };
+            for i in 0..count {
+                if r.read_bit()? {
+                    r.skip_scaling_list(if i < 6 { 16 } else { 64 })?;
+                }
+            }
+        }
+    }
+
+    r.read_ue_max(12)?; // log2_max_frame_num_minus4
+    match r.read_ue_max(2)? {
+        0 => {
+            r.read_ue_max(12)?; // log2_max_pic_order_cnt_lsb_minus4
+        }
+        1 => {
+            r.skip(1)?; // delta_pic_order_always_zero_flag
+            r.read_se()?; // offset_for_non_ref_pic
+            r.read_se()?; // offset_for_top_to_bottom_field
+            for _ in 0..r.read_ue_max(255)? {
+                r.read_se()?;
+            }
+        }
+        _ => {}
+    }
+
+    r.read_ue()?; // max_num_ref_frames
+    r.skip(1)?; // gaps_in_frame_num_value_allowed_flag
+
+    let width_in_mbs = r.read_ue()?.checked_add(1)?;
+    let height_in_map_units = r.read_ue()?.checked_add(1)?;
+    let frame_mbs_only = r.read_bit()?;
+    if !frame_mbs_only {
+        r.skip(1)?; // mb_adaptive_frame_field_flag
+    }
+    r.skip(1)?; // direct_8x8_inference_flag
+
+    let (mut crop_left, mut crop_right, mut crop_top, mut crop_bottom) = (0, 0, 0, 0);
+    if r.read_bit()? {
+        crop_left = r.read_ue()?;
+        crop_right = r.read_ue()?;
+        crop_top = r.read_ue()?;
+        crop_bottom = r.read_ue()?;
+    }
+
+    let field_factor = if frame_mbs_only { 1 } else { 2 };
+    let (crop_unit_x, crop_unit_y) = if separate_colour_plane || chroma_format_idc == 0 {
+        (1, field_factor)
+    } else {
+        let sub_width = if chroma_format_idc == 3 { 1 } else { 2 };
+        let sub_height = if chroma_format_idc == 1 { 2 } else { 1 };
+        (sub_width, sub_height * field_factor)
+    };
+
+    let crop_x = crop_left
+        .checked_add(crop_right)?
+        .checked_mul(crop_unit_x)?;
+    let crop_y = crop_top
+        .checked_add(crop_bottom)?
+        .checked_mul(crop_unit_y)?;
+    let width = width_in_mbs.checked_mul(16)?.checked_sub(crop_x)?;
+    let height = height_in_map_units
+        .checked_mul(16 * field_factor)?
+        .checked_sub(crop_y)?;
+
+    Some((width, height))
+}
+
+/// Sample rate and channel count of an AAC AudioSpecificConfig
+pub(crate) fn aac_sample_rate(config: &[u8]) -> Option<(u32, u8)> {
+    const RATES: [u32; 13] = [
+        96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+    ];
+
+    let mut r = BitReader::new(config);
+
+    if r.read_bits(5)? == 31 {
+        r.skip(6)?; // audioObjectTypeExt
+    }
+
+    let rate = match r.read_bits(4)? {
+        15 => r.read_bits(24)?,
+        index => *RATES.get(index as usize)?,
+    };
+    let channels = r.read_bits(4)? as u8;
+
+    Some((rate, channels))
+}
+
+fn remove_emulation_prevention(data: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(data.len());
+    let mut zeros = 0;
+    for &b in data {
+        if zeros >= 2 && b == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if b == 0 { zeros + 1 } else { 0 };
+        rbsp.push(b);
+    }
+    rbsp
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn read_bit(&mut self) -> Option<bool> {
+        let byte = self.data.get(self.position / 8)?;
+        let bit = byte >> (7 - self.position % 8) & 1;
+        self.position += 1;
+        Some(bit != 0)
+    }
+
+    fn read_bits(&mut self, n: u32) -> Option<u32> {
+        let mut value = 0;
+        for _ in 0..n {
+            value = value << 1 | self.read_bit()? as u32;
+        }
+        Some(value)
+    }
+
+    fn skip(&mut self, n: usize) -> Option<()> {
+        if self.position + n > self.data.len() * 8 {
+            return None;
+        }
+        self.position += n;
+        Some(())
+    }
+
+    /// Unsigned Exp-Golomb code
+    fn read_ue(&mut self) -> Option<u32> {
+        let mut leading_zeros = 0;
+        while !self.read_bit()? {
+            leading_zeros += 1;
+            if leading_zeros > 31 {
+                return None;
+            }
+        }
+        Some((1 << leading_zeros) - 1 + self.read_bits(leading_zeros)?)
+    }
+
+    /// Unsigned Exp-Golomb code of a syntax element whose range ends at `max`
+    fn read_ue_max(&mut self, max: u32) -> Option<u32> {
+        self.read_ue().filter(|&value| value <= max)
+    }
+
+    /// Signed Exp-Golomb code
+    fn read_se(&mut self) -> Option<i32> {
+        let k = self.read_ue()? as i64;
+        Some(if k % 2 == 1 { (k + 1) / 2 } else { -(k / 2) } as i32)
+    }
+
+    fn skip_scaling_list(&mut self, size: usize) -> Option<()> {
+        let (mut last, mut next) = (8i32, 8i32);
+        for _ in 0..size {
+            if next != 0 {
+                let delta_scale = self.read_se()?;
+                if !(-128..=127).contains(&delta_scale) {
+                    return None;
+                }
+                next = (last + delta_scale + 256) % 256;
+            }
+            if next != 0 {
+                last = next;
+            }
+        }
+        Some(())
+    }
+}
+
+#[test]
+fn parse_sps_dimensions() {
+    // High profile, 1920x1088 macroblocks cropped to 1920x1080
+    let sps = [
+        0x67, 0x64, 0x00, 0x28, 0xac, 0xd9, 0x40, 0x78, 0x02, 0x27, 0xe5, 0x40,
+    ];
+    assert_eq!(Some((1920, 1080)), sps_dimensions(&sps));
+
+    let mut record = vec![0x01, 0x64, 0x00, 0x28, 0xff, 0xe1, 0x00, sps.len() as u8];
+    record.extend_from_slice(&sps);
+    assert_eq!(Some((1920, 1080)), avc_dimensions(&record));
+
+    // AAC LC, 44100Hz, stereo
+    assert_eq!(Some((44100, 2)), aac_sample_rate(&[0x12, 0x10]));
+}
+
+#[test]
+fn reject_oversized_sps() {
+    // Baseline profile SPS from the fields after level_idc written as bits
+    let sps = |bits: &str| {
+        let bits: Vec<u8> = bits
+            .bytes()
+            .filter(|b| *b != b' ')
+            .map(|b| b - b'0')
+            .collect();
+        let mut nal = vec![0x67, 0x42, 0x00, 0x1e];
+        nal.extend(bits.chunks(8).map(|chunk| {
+            chunk
+                .iter()
+                .chain(std::iter::repeat(&0))
+                .take(8)
+                .fold(0, |byte, bit| byte << 1 | bit)
+        }));
+        nal
+    };
+    let max_ue = format!("{}1{}", "0".repeat(31), "1".repeat(31));
+
+    // sps_id, log2_max_frame_num, pic_order_cnt_type 2, max_num_ref_frames,
+    // gaps flag, then 1 macroblock wide and high, frame_mbs_only, direct_8x8
+    assert_eq!(
+        Some((16, 16)),
+        sps_dimensions(&sps("1 1 011 1 0 1 1 1 1 0"))
+    );
+
+    // width_in_mbs * 16 overflows
+    let width = format!("1 1 011 1 0 {} 1 1 1 0", max_ue);
+    assert_eq!(None, sps_dimensions(&sps(&width)));
+
+    // crop_left + crop_right overflows
+    let crop = format!("1 1 011 1 0 1 1 1 1 1 {} {} 1 1", max_ue, max_ue);
+    assert_eq!(None, sps_dimensions(&sps(&crop)));
+
+    // pic_order_cnt_type out of range
+    assert_eq!(None, sps_dimensions(&sps("1 1 00100 1 0 1 1 1 1 0")));
+}
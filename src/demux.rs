@@ -0,0 +1,358 @@
+//! Sans-IO FLV parser, the core of every reader backend.
+
+use crate::error::ParseError;
+use crate::types::{Header, TagHeader};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// What [`FlvDemuxer`] found in its input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// The file header, emitted once the extension up to `data_offset` has
+    /// been read, see [`FlvDemuxer::header_extension`]
+    Header(Header),
+    PreviousTagSize(u32),
+    TagHeader(TagHeader),
+
+    /// A part of the tag body, borrowed from the input
+    PayloadChunk(&'a [u8]),
+
+    /// The whole tag body has been emitted
+    TagEnd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Header,
+    HeaderExtension { header: Header, remaining: usize },
+    PreviousTagSize,
+    TagHeader,
+    Payload { remaining: usize },
+    TagEnd,
+}
+
+/// An incremental push parser for FLV streams.
+///
+/// Byte chunks of any size are fed with [`FlvDemuxer::push`] as they arrive
+/// and parsed into [`Event`]s. Payloads are emitted as slices of the input,
+/// so memory use is bounded by the size of a tag header whatever the size of
+/// the tags, apart from the header extension of which the first
+/// [`Header::MAX_EXTENSION_SIZE`] bytes are kept, see
+/// [`FlvDemuxer::with_max_header_extension`].
+///
+/// ```
+/// use flv::{Event, FlvDemuxer};
+///
+/// # fn main() -> Result<(), flv::ParseError> {
+/// let mut demuxer = FlvDemuxer::new();
+/// for chunk in [&b"FLV\x01\x05\x00\x00"[..], &b"\x00\x09\x00\x00\x00\x00"[..]] {
+///     for event in demuxer.push(chunk) {
+///         if let Event::Header(header) = event? {
+///             assert!(header.audio_flag && header.video_flag);
+///         }
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FlvDemuxer {
+    state: State,
+
+    /// the fixed size field being read
+    buffer: [u8; TagHeader::SIZE],
+    filled: usize,
+
+    extension: Vec<u8>,
+    max_header_extension: usize,
+}
+
+impl FlvDemuxer {
+    /// Creates a demuxer expecting a file header.
+    pub fn new() -> Self {
+        Self {
+            state: State::Header,
+            buffer: [0; TagHeader::SIZE],
+            filled: 0,
+            extension: vec![],
+            max_header_extension: Header::MAX_EXTENSION_SIZE,
+        }
+    }
+
+    /// Limits the header extension bytes to keep, the rest of the extension
+    /// is skipped.
+    pub fn with_max_header_extension(mut self, max_header_extension: usize) -> Self {
+        self.max_header_extension = max_header_extension;
+        self
+    }
+
+    /// Parses `input`, the returned iterator yields the events found in it.
+    ///
+    /// The whole input is consumed only once the iterator returns `None`,
+    /// bytes completing no event are kept for the next push. After an error
+    /// the iterator ends and the rest of the input is dropped.
+    pub fn push<'d, 'a>(&'d mut self, input: &'a [u8]) -> Events<'d, 'a> {
+        Events {
+            demuxer: self,
+            input,
+            failed: false,
+        }
+    }
+
+    /// Parses the next event from the front of `input`, returning the number
+    /// of bytes consumed and the event if one was completed.
+    pub fn feed<'a>(&mut self, input: &'a [u8]) -> Result<(usize, Option<Event<'a>>), ParseError> {
+        match self.state {
+            State::Header => {
+                let consumed = self.fill(input, Header::SIZE);
+                if self.filled < Header::SIZE {
+                    return Ok((consumed, None));
+                }
+
+                self.filled = 0;
+                let mut buffer = [0u8; Header::SIZE];
+                buffer.copy_from_slice(&self.buffer[..Header::SIZE]);
+                let header = Header::try_from(buffer)?;
+
+                self.extension.clear();
+                self.state = State::HeaderExtension {
+                    header,
+                    remaining: header.data_offset as usize - Header::SIZE,
+                };
+
+                let (extension, event) = self.feed(&input[consumed..])?;
+                Ok((consumed + extension, event))
+            }
+            State::HeaderExtension { header, remaining } => {
+                let n = remaining.min(input.len());
+                let kept = (self.max_header_extension - self.extension.len()).min(n);
+                self.extension.extend_from_slice(&input[..kept]);
+                if n < remaining {
+                    self.state = State::HeaderExtension {
+                        header,
+                        remaining: remaining - n,
+                    };
+                    return Ok((n, None));
+                }
+
+                self.state = State::PreviousTagSize;
+                Ok((n, Some(Event::Header(header))))
+            }
+            State::PreviousTagSize => {
+                let consumed = self.fill(input, 4);
+                if self.filled < 4 {
+                    return Ok((consumed, None));
+                }
+
+                self.filled = 0;
+                self.state = State::TagHeader;
+                let [a, b, c, d, ..] = self.buffer;
+                Ok((
+                    consumed,
+                    Some(Event::PreviousTagSize(u32::from_be_bytes([a, b, c, d]))),
+                ))
+            }
+            State::TagHeader => {
+                let consumed = self.fill(input, TagHeader::SIZE);
+                if self.filled < TagHeader::SIZE {
+                    return Ok((consumed, None));
+                }
+
+                self.filled = 0;
+                let header = TagHeader::from(self.buffer);
+                self.state = match header.data_size {
+                    0 => State::TagEnd,
+                    size => State::Payload {
+                        remaining: size as usize,
+                    },
+                };
+                Ok((consumed, Some(Event::TagHeader(header))))
+            }
+            State::Payload { remaining } => {
+                let n = remaining.min(input.len());
+                if n == 0 {
+                    return Ok((0, None));
+                }
+
+                self.state = match remaining - n {
+                    0 => State::TagEnd,
+                    remaining => State::Payload { remaining },
+                };
+                Ok((n, Some(Event::PayloadChunk(&input[..n]))))
+            }
+            State::TagEnd => {
+                self.state = State::PreviousTagSize;
+                Ok((0, Some(Event::TagEnd)))
+            }
+        }
+    }
+
+    /// Number of bytes needed to complete the field being read, the rest of
+    /// the payload inside a tag body
+    pub fn needed(&self) -> usize {
+        match self.state {
+            State::Header => Header::SIZE - self.filled,
+            State::HeaderExtension { remaining, .. } => remaining,
+            State::PreviousTagSize => 4 - self.filled,
+            State::TagHeader => TagHeader::SIZE - self.filled,
+            State::Payload { remaining } => remaining,
+            State::TagEnd => 0,
+        }
+    }
+
+    /// The field being read
+    #[cfg(feature = "tokio-codec")]
+    pub(crate) fn field(&self) -> crate::FieldKind {
+        use crate::FieldKind;
+
+        match self.state {
+            State::Header | State::HeaderExtension { .. } => FieldKind::Header,
+            State::PreviousTagSize => FieldKind::PreviousTagSize,
+            State::TagHeader => FieldKind::TagHeader,
+            State::Payload { .. } | State::TagEnd => FieldKind::TagData,
+        }
+    }
+
+    /// Header bytes between [`Header::SIZE`] and `data_offset` of the last
+    /// header parsed, empty for a standard header. Only the first bytes are
+    /// kept, up to the limit set by [`FlvDemuxer::with_max_header_extension`].
+    pub fn header_extension(&self) -> &[u8] {
+        &self.extension
+    }
+
+    /// Expects a file header next, for a new stream.
+    pub fn reset(&mut self) {
+        self.state = State::Header;
+        self.filled = 0;
+    }
+
+    /// Expects a tag header next, after the input was moved to the start of
+    /// a tag, for example to a keyframe position from the metadata.
+    pub fn seek_to_tag(&mut self) {
+        self.state = State::TagHeader;
+        self.filled = 0;
+    }
+
+    /// Expects a PreviousTagSize field next, after the input was moved to
+    /// the end of a tag.
+    pub fn seek_to_previous_tag_size(&mut self) {
+        self.state = State::PreviousTagSize;
+        self.filled = 0;
+    }
+
+    /// Buffers up to `size` bytes of a fixed size field.
+    fn fill(&mut self, input: &[u8], size: usize) -> usize {
+        let n = (size - self.filled).min(input.len());
+        self.buffer[self.filled..self.filled + n].copy_from_slice(&input[..n]);
+        self.filled += n;
+        n
+    }
+}
+
+impl Default for FlvDemuxer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Iterator over the events of a chunk, see [`FlvDemuxer::push`]
+#[derive(Debug)]
+pub struct Events<'d, 'a> {
+    demuxer: &'d mut FlvDemuxer,
+    input: &'a [u8],
+    failed: bool,
+}
+
+impl<'d, 'a> Iterator for Events<'d, 'a> {
+    type Item = Result<Event<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.failed {
+            match self.demuxer.feed(self.input) {
+                Ok((consumed, event)) => {
+                    self.input = &self.input[consumed..];
+                    match event {
+                        Some(event) => return Some(Ok(event)),
+                        None if self.input.is_empty() => return None,
+                        None => {}
+                    }
+                }
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[test]
+fn demux_byte_by_byte() {
+    let mut input = b"FLV\x01\x01\x00\x00\x00\x0bxy\x00\x00\x00\x00".to_vec();
+    // a video tag of 3 bytes and an empty script tag
+    input.extend_from_slice(&[9, 0, 0, 3, 0, 0, 40, 0, 0, 0, 0, 0x17, 1, 2, 0, 0, 0, 14]);
+    input.extend_from_slice(&[18, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 11]);
+
+    let mut demuxer = FlvDemuxer::new();
+    let mut events = vec![];
+    let mut payload = vec![];
+    for b in input.chunks(1) {
+        for event in demuxer.push(b) {
+            match event.unwrap() {
+                Event::PayloadChunk(chunk) => payload.extend_from_slice(chunk),
+                Event::TagHeader(header) => events.push(format!("tag {}", header.timestamp)),
+                event => events.push(format!("{:?}", event)),
+            }
+        }
+    }
+
+    assert_eq!(b"xy", demuxer.header_extension());
+    assert_eq!(&[0x17, 1, 2], &payload[..]);
+    assert_eq!(
+        vec![
+            "Header(Header { version: 1, audio_flag: false, video_flag: true, data_offset: 11 })",
+            "PreviousTagSize(0)",
+            "tag 40",
+            "TagEnd",
+            "PreviousTagSize(14)",
+            "tag 0",
+            "TagEnd",
+            "PreviousTagSize(11)",
+        ],
+        events
+    );
+
+    // only the start of a big extension is kept
+    let mut demuxer = FlvDemuxer::new().with_max_header_extension(4);
+    let mut input = b"FLV\x01\x05\x00\x01\x00\x09".to_vec();
+    input.resize(0x1_0009, 7);
+    input.extend_from_slice(&[0, 0, 0, 0]);
+    let events: Vec<_> = input
+        .chunks(1000)
+        .flat_map(|chunk| demuxer.push(chunk).map(Result::unwrap).collect::<Vec<_>>())
+        .collect();
+    assert_eq!(
+        vec![
+            Event::Header(Header {
+                version: 1,
+                audio_flag: true,
+                video_flag: true,
+                data_offset: 0x1_0009,
+            }),
+            Event::PreviousTagSize(0),
+        ],
+        events
+    );
+    assert_eq!(&[7; 4], demuxer.header_extension());
+
+    let mut demuxer = FlvDemuxer::new();
+    let mut events = demuxer.push(b"FLX\x01\x05\x00\x00\x00\x09");
+    assert!(matches!(
+        events.next(),
+        Some(Err(ParseError::HeaderSignature(b'F', b'L', b'X')))
+    ));
+    assert!(events.next().is_none());
+}
@@ -0,0 +1,241 @@
+use crate::error::{Error, ParseError, Result};
+use crate::types::{SoundFormat, TagType, VideoCodecId};
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// EncryptionTagHeader, present at the start of the body of a filtered tag
+/// (after the audio or video tag header fields)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EncryptionTagHeader {
+    /// Number of filters applied to the packet, must be 1
+    pub num_filters: u8,
+
+    /// "Encryption" or "SE"
+    pub filter_name: String,
+
+    /// Length of the FilterParams in bytes
+    pub length: u32,
+}
+
+impl EncryptionTagHeader {
+    pub const ENCRYPTION: &'static str = "Encryption";
+    pub const SELECTIVE_ENCRYPTION: &'static str = "SE";
+}
+
+/// FilterParams following the encryption tag header
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FilterParams {
+    /// EncryptionFilterParams
+    Encryption { iv: [u8; 16] },
+
+    /// SelectiveEncryptionFilterParams, `iv` is present only if the access
+    /// unit is encrypted
+    SelectiveEncryption { iv: Option<[u8; 16]> },
+
+    /// Params of an unknown filter
+    Unknown(Vec<u8>),
+}
+
+/// Encryption header and filter params of a filtered tag
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EncryptionFilter {
+    pub header: EncryptionTagHeader,
+    pub params: FilterParams,
+}
+
+impl EncryptionFilter {
+    /// Reads the encryption header and filter params from the front of `buf`
+    /// and advances it to the filtered data.
+    pub fn read(buf: &mut &[u8]) -> core::result::Result<Self, ParseError> {
+        let num_filters = take(buf, 1)?[0];
+        let name_len = take(buf, 2)?;
+        let name_len = u16::from_be_bytes([name_len[0], name_len[1]]) as usize;
+        let filter_name = core::str::from_utf8(take(buf, name_len)?)
+            .map_err(|_| ParseError::EncryptionHeader)?
+            .to_owned();
+        let length = take(buf, 3)?;
+        let length = u32::from_be_bytes([0, length[0], length[1], length[2]]);
+
+        let params = take(buf, length as usize)?;
+        let params = match filter_name.as_str() {
+            EncryptionTagHeader::ENCRYPTION => FilterParams::Encryption {
+                iv: iv(params.get(..16))?,
+            },
+            EncryptionTagHeader::SELECTIVE_ENCRYPTION => {
+                let encrypted_au = params.first().ok_or(ParseError::EncryptionHeader)? & 0x80;
+                FilterParams::SelectiveEncryption {
+                    iv: match encrypted_au {
+                        0 => None,
+                        _ => Some(iv(params.get(1..17))?),
+                    },
+                }
+            }
+            _ => FilterParams::Unknown(params.to_vec()),
+        };
+
+        Ok(Self {
+            header: EncryptionTagHeader {
+                num_filters,
+                filter_name,
+                length,
+            },
+            params,
+        })
+    }
+
+    /// Appends the encryption header and filter params to `buf`, the length
+    /// in the header is computed from the params.
+    ///
+    /// Fails with [`Error::DataSize`](crate::Error::DataSize) if the filter
+    /// name or the params are too long for their length fields.
+    pub fn write(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let mut params = vec![];
+        match &self.params {
+            FilterParams::Encryption { iv } => params.extend_from_slice(iv),
+            FilterParams::SelectiveEncryption { iv: None } => params.push(0),
+            FilterParams::SelectiveEncryption { iv: Some(iv) } => {
+                params.push(0x80);
+                params.extend_from_slice(iv);
+            }
+            FilterParams::Unknown(data) => params.extend_from_slice(data),
+        }
+
+        let name = self.header.filter_name.as_bytes();
+        if name.len() > u16::MAX as usize {
+            return Err(Error::DataSize(name.len()));
+        }
+        if params.len() > 0x00ffffff {
+            return Err(Error::DataSize(params.len()));
+        }
+
+        buf.push(self.header.num_filters);
+        buf.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        buf.extend_from_slice(name);
+        buf.extend_from_slice(&(params.len() as u32).to_be_bytes()[1..]);
+        buf.extend_from_slice(&params);
+        Ok(())
+    }
+
+    /// Number of bytes at the start of a tag body that precede the
+    /// encryption header: the audio or video tag header including the
+    /// AAC or AVC packet fields.
+    pub fn offset(tag_type: TagType, body: &[u8]) -> usize {
+        match (tag_type, body.first()) {
+            (TagType::Audio, Some(&b)) => match SoundFormat::try_from(b) {
+                Ok(SoundFormat::AAC) => 2,
+                _ => 1,
+            },
+            (TagType::Video, Some(&b)) => match VideoCodecId::try_from(b) {
+                Ok(VideoCodecId::AVC) => 5,
+                _ => 1,
+            },
+            _ => 0,
+        }
+    }
+}
+
+/// Decrypts the data of filtered tags, see
+/// [`FlvReader::with_decryptor`](crate::io::FlvReader::with_decryptor)
+pub trait Decryptor {
+    /// Decrypts `data`, the part of a tag body following the filter params.
+    fn decrypt(&mut self, filter: &EncryptionFilter, data: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Replaces the encryption header, the filter params and the filtered data
+/// of a tag body with the decrypted data.
+#[cfg(any(feature = "io-std", feature = "io-tokio", feature = "io-futures"))]
+pub(crate) fn decrypt_tag(
+    decryptor: &mut dyn Decryptor,
+    header: crate::TagHeader,
+    data: &[u8],
+) -> Result<(crate::TagHeader, Vec<u8>)> {
+    let offset = EncryptionFilter::offset(header.tag_type, data).min(data.len());
+    let mut filtered = &data[offset..];
+    let filter = EncryptionFilter::read(&mut filtered)?;
+
+    let mut body = data[..offset].to_vec();
+    body.extend(decryptor.decrypt(&filter, filtered)?);
+
+    let header = crate::TagHeader {
+        filter: false,
+        data_size: body.len() as u32,
+        ..header
+    };
+
+    Ok((header, body))
+}
+
+fn take<'a>(buf: &mut &'a [u8], n: usize) -> core::result::Result<&'a [u8], ParseError> {
+    if buf.len() < n {
+        return Err(ParseError::EncryptionHeader);
+    }
+    let (head, tail) = buf.split_at(n);
+    *buf = tail;
+    Ok(head)
+}
+
+fn iv(bytes: Option<&[u8]>) -> core::result::Result<[u8; 16], ParseError> {
+    let mut iv = [0u8; 16];
+    iv.copy_from_slice(bytes.ok_or(ParseError::EncryptionHeader)?);
+    Ok(iv)
+}
+
+#[test]
+fn encryption_filter_round_trip() {
+    for params in [
+        FilterParams::Encryption { iv: [7; 16] },
+        FilterParams::SelectiveEncryption { iv: None },
+        FilterParams::SelectiveEncryption { iv: Some([3; 16]) },
+    ] {
+        let filter_name = match params {
+            FilterParams::Encryption { .. } => EncryptionTagHeader::ENCRYPTION,
+            _ => EncryptionTagHeader::SELECTIVE_ENCRYPTION,
+        };
+
+        let mut buf = vec![];
+        EncryptionFilter {
+            header: EncryptionTagHeader {
+                num_filters: 1,
+                filter_name: filter_name.to_owned(),
+                length: 0,
+            },
+            params: params.clone(),
+        }
+        .write(&mut buf)
+        .unwrap();
+        buf.extend_from_slice(b"data");
+
+        let mut data = &buf[..];
+        let filter = EncryptionFilter::read(&mut data).unwrap();
+        assert_eq!(params, filter.params);
+        assert_eq!(b"data", data);
+    }
+
+    // lengths that don't fit in their fields
+    let filter = EncryptionFilter {
+        header: EncryptionTagHeader {
+            num_filters: 1,
+            filter_name: "x".repeat(1 << 16),
+            length: 0,
+        },
+        params: FilterParams::Unknown(vec![]),
+    };
+    assert!(matches!(
+        filter.write(&mut vec![]),
+        Err(Error::DataSize(0x10000))
+    ));
+    let filter = EncryptionFilter {
+        header: EncryptionTagHeader {
+            filter_name: "x".to_owned(),
+            ..filter.header
+        },
+        params: FilterParams::Unknown(vec![0; 1 << 24]),
+    };
+    assert!(matches!(
+        filter.write(&mut vec![]),
+        Err(Error::DataSize(0x1000000))
+    ));
+}
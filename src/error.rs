@@ -1,27 +1,141 @@
+use core::fmt;
 use thiserror::Error as ThisError;
 
 /// A `Result` typedef to use with the `flv::Error` type
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
 /// flv error type
 #[derive(ThisError, Debug)]
 pub enum Error {
     #[error(transparent)]
     Parse(#[from] ParseError),
-    #[cfg(feature = "io-std")]
+    #[cfg(feature = "std")]
     #[error(transparent)]
     Io(#[from] std::io::Error),
     #[error("data size is too long: {0}")]
     DataSize(usize),
+    #[error("tag type doesn't fit in 5 bits: {0}")]
+    TagType(u8),
+    #[error("reserved bits of a tag header don't fit in 2 bits: {0}")]
+    TagReserved(u8),
+    #[error("onMetaData script tag not found")]
+    MetaDataNotFound,
+    #[error("end of input")]
+    Eof,
+    #[error("late packet: {timestamp}ms, after {last}ms was written")]
+    LatePacket { timestamp: i32, last: i32 },
+    #[cfg(feature = "alloc")]
+    #[error("decryption failed: {0}")]
+    Decrypt(alloc::boxed::Box<dyn core::error::Error + Send + Sync>),
+    #[cfg(feature = "alloc")]
+    #[error("{source} ({location})")]
+    Located {
+        location: Location,
+        source: alloc::boxed::Box<Error>,
+    },
+    #[cfg(all(feature = "serde", feature = "alloc"))]
+    #[error("AMF0 serde error: {0}")]
+    Serde(alloc::string::String),
+}
+
+impl Error {
+    /// Attaches `location` to the error, unless it already has one.
+    #[cfg(any(feature = "io-std", feature = "io-tokio", feature = "io-futures"))]
+    pub(crate) fn at(self, location: Location) -> Self {
+        match self {
+            located @ Error::Located { .. } => located,
+            error => Error::Located {
+                location,
+                source: alloc::boxed::Box::new(error),
+            },
+        }
+    }
+
+    /// Where the error occurred, when it was raised by a reader
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            #[cfg(feature = "alloc")]
+            Error::Located { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+
+    /// The error without its location
+    pub fn inner(&self) -> &Error {
+        match self {
+            #[cfg(feature = "alloc")]
+            Error::Located { source, .. } => source.inner(),
+            error => error,
+        }
+    }
+
+    /// The input ended cleanly, before the first byte of a field.
+    pub fn is_eof(&self) -> bool {
+        matches!(self.inner(), Error::Eof)
+    }
+
+    /// The input ended in the middle of a field.
+    pub fn is_truncated(&self) -> bool {
+        matches!(self.inner(), Error::Parse(ParseError::Truncated(_)))
+    }
+}
+
+/// The part of the input being decoded, see [`Location`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    /// The file header, with its extension up to `data_offset`
+    Header,
+    PreviousTagSize,
+    TagHeader,
+    TagData,
+    AudioDataHeader,
+    VideoDataHeader,
+}
+
+impl fmt::Display for FieldKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FieldKind::Header => "file header",
+            FieldKind::PreviousTagSize => "PreviousTagSize",
+            FieldKind::TagHeader => "tag header",
+            FieldKind::TagData => "tag data",
+            FieldKind::AudioDataHeader => "audio data header",
+            FieldKind::VideoDataHeader => "video data header",
+        })
+    }
+}
+
+/// Where a reader failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    /// Offset of the start of the field
+    pub offset: u64,
+
+    /// Index of the tag the field belongs to, `None` for the file header and
+    /// PreviousTagSize0, or when unknown after a seek
+    pub tag_index: Option<u64>,
+    pub field: FieldKind,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.field, self.offset)?;
+        if let Some(tag_index) = self.tag_index {
+            write!(f, " in tag {}", tag_index)?;
+        }
+        Ok(())
+    }
 }
 
 /// parse error
-#[derive(ThisError, Debug, Eq, PartialEq)]
+#[derive(ThisError, Debug, Clone, Eq, PartialEq)]
 pub enum ParseError {
     #[error("invalid header signature: 0x{0:X}, 0x{1:X}, 0x{2:X}")]
     HeaderSignature(u8, u8, u8),
     #[error("invalid reserved type flags format: 0x{0:X}")]
     HeaderTypeFlagsReserved(u8),
+    #[error("invalid header data offset: {0}")]
+    HeaderDataOffset(u32),
     #[error("invalid sound format: 0x{0:X}")]
     SoundFormat(u8),
     #[error("invalid sound rate: 0x{0:X}")]
@@ -36,4 +150,70 @@ pub enum ParseError {
     VideoCodecId(u8),
     #[error("invalid seek flag: 0x{0:X}")]
     SeekFlag(u8),
+    #[error("invalid amf0 type marker: 0x{0:X}")]
+    Amf0Marker(u8),
+    #[error("unexpected end of amf0 data")]
+    Amf0Eof,
+    #[error("invalid utf-8 string in amf0 data")]
+    Amf0String,
+    #[error("amf0 data nested too deeply")]
+    Amf0Depth,
+    #[error("invalid amf3 type marker: 0x{0:X}")]
+    Amf3Marker(u8),
+    #[error("invalid amf3 reference: {0}")]
+    Amf3Reference(u32),
+    #[error("unsupported externalizable amf3 object")]
+    Amf3Externalizable,
+    #[error("amf3 data nested too deeply")]
+    Amf3Depth,
+    #[error("amf3 data too big once references are resolved")]
+    Amf3Size,
+    #[error("invalid metadata value")]
+    MetaData,
+    #[error("invalid script data message")]
+    ScriptData,
+    #[error("invalid encryption tag header")]
+    EncryptionHeader,
+    #[error("non-zero stream id: {0}")]
+    StreamId(u32),
+    #[error("missing audio or video data header")]
+    TagData,
+    #[error("input truncated at offset {0}")]
+    Truncated(u64),
+}
+
+/// How readers handle field values and anomalies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    /// Unknown field values and anomalies such as non-zero stream ids fail
+    /// the read.
+    #[default]
+    Strict,
+
+    /// Unknown field values are kept as `Unknown` variants and every anomaly
+    /// is recorded as a [`Warning`].
+    Lenient,
+
+    /// Like lenient, but nothing is checked nor recorded.
+    Raw,
+}
+
+/// An anomaly found by a reader in lenient mode, which would have failed the
+/// read in strict mode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub location: Location,
+    pub anomaly: ParseError,
+}
+
+impl Warning {
+    /// Warnings a reader keeps until they are taken, the following ones are
+    /// only counted
+    pub const MAX_KEPT: usize = 1024;
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.anomaly, self.location)
+    }
 }
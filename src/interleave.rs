@@ -0,0 +1,191 @@
+/// What an `InterleavingWriter` does with a tag older than the last one it
+/// wrote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LatePolicy {
+    /// The tag is discarded.
+    #[default]
+    Drop,
+
+    /// The tag is written with the timestamp of the last tag written.
+    Clamp,
+
+    /// The write fails with [`Error::LatePacket`].
+    ///
+    /// [`Error::LatePacket`]: crate::Error::LatePacket
+    Error,
+}
+
+/// Options of the `InterleavingWriter`s
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interleaving {
+    /// How long in milliseconds a tag waits for the other track, compared
+    /// to the newest timestamp received on any track
+    pub latency: i32,
+    pub late: LatePolicy,
+}
+
+impl Default for Interleaving {
+    fn default() -> Self {
+        Self {
+            latency: 500,
+            late: LatePolicy::default(),
+        }
+    }
+}
+
+/// The queue behind the `InterleavingWriter`s of every backend
+#[cfg(any(feature = "io-std", feature = "io-tokio", feature = "io-futures"))]
+pub(crate) mod queue {
+    use super::{Interleaving, LatePolicy};
+    use crate::{AudioDataHeader, Error, Result, VideoDataHeader};
+    use alloc::collections::BTreeMap;
+    use alloc::vec::Vec;
+
+    #[derive(Debug)]
+    pub(crate) enum Media {
+        Audio(AudioDataHeader),
+        Video(VideoDataHeader),
+    }
+
+    #[derive(Debug)]
+    pub(crate) struct Packet {
+        pub(crate) timestamp: i32,
+        pub(crate) media: Media,
+        pub(crate) data: Vec<u8>,
+    }
+
+    /// The ordering shared by the `InterleavingWriter`s of every backend.
+    ///
+    /// Each track is expected in timestamp order. A tag is released once the
+    /// other track has reached its timestamp, or once it falls out of the
+    /// latency window when the other track lags or is absent.
+    #[derive(Debug)]
+    pub(crate) struct InterleaverCore {
+        options: Interleaving,
+
+        /// tags by timestamp, then by arrival
+        queue: BTreeMap<(i32, u64), Packet>,
+        arrivals: u64,
+
+        /// newest timestamp received on each track
+        audio: Option<i32>,
+        video: Option<i32>,
+
+        /// timestamp of the last tag released
+        released: Option<i32>,
+        pub(crate) late: u64,
+    }
+
+    impl InterleaverCore {
+        pub(crate) fn new(options: Interleaving) -> Self {
+            Self {
+                options,
+                queue: BTreeMap::new(),
+                arrivals: 0,
+                audio: None,
+                video: None,
+                released: None,
+                late: 0,
+            }
+        }
+
+        /// Queues `packet`, applying the late policy if it is older than the
+        /// last tag released. A dropped or rejected tag leaves the track as it
+        /// was.
+        pub(crate) fn push(&mut self, mut packet: Packet) -> Result<()> {
+            match self.released {
+                Some(released) if packet.timestamp < released => {
+                    self.late += 1;
+                    match self.options.late {
+                        LatePolicy::Drop => return Ok(()),
+                        LatePolicy::Clamp => packet.timestamp = released,
+                        LatePolicy::Error => {
+                            return Err(Error::LatePacket {
+                                timestamp: packet.timestamp,
+                                last: released,
+                            })
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            let track = match packet.media {
+                Media::Audio(_) => &mut self.audio,
+                Media::Video(_) => &mut self.video,
+            };
+            *track = Some(track.map_or(packet.timestamp, |t| t.max(packet.timestamp)));
+
+            self.queue.insert((packet.timestamp, self.arrivals), packet);
+            self.arrivals += 1;
+            Ok(())
+        }
+
+        /// The next tag to write, if it can't be preceded by a tag yet to come.
+        pub(crate) fn pop(&mut self) -> Option<Packet> {
+            let &(timestamp, _) = self.queue.keys().next()?;
+
+            let reached = match (self.audio, self.video) {
+                (Some(audio), Some(video)) => timestamp <= audio.min(video),
+                _ => false,
+            };
+            let newest = self.audio.max(self.video).unwrap_or(timestamp);
+            let expired = newest as i64 - timestamp as i64 >= self.options.latency as i64;
+
+            if reached || expired {
+                self.pop_any()
+            } else {
+                None
+            }
+        }
+
+        /// The next tag to write, without waiting for the other track.
+        pub(crate) fn pop_any(&mut self) -> Option<Packet> {
+            let (_, packet) = self.queue.pop_first()?;
+            self.released = Some(packet.timestamp);
+            Some(packet)
+        }
+
+        pub(crate) fn len(&self) -> usize {
+            self.queue.len()
+        }
+    }
+
+    #[test]
+    fn reject_before_update() {
+        use crate::{SoundFormat, SoundRate, SoundSize, SoundType, VideoCodecId, VideoFrameType};
+
+        let packet = |timestamp, media| Packet {
+            timestamp,
+            media,
+            data: vec![],
+        };
+        let audio = Media::Audio(AudioDataHeader {
+            sound_format: SoundFormat::AAC,
+            sound_rate: SoundRate::R44kHz,
+            sound_size: SoundSize::S16Bit,
+            sound_type: SoundType::Stereo,
+        });
+        let video = Media::Video(VideoDataHeader {
+            frame_type: VideoFrameType::KeyFrame,
+            codec_id: VideoCodecId::AVC,
+        });
+
+        let mut core = InterleaverCore::new(Interleaving {
+            latency: 100,
+            late: LatePolicy::Error,
+        });
+        core.push(packet(200, audio)).unwrap();
+        assert_eq!(200, core.pop_any().unwrap().timestamp);
+
+        // the rejected tag doesn't start the video track
+        assert!(matches!(
+            core.push(packet(100, video)),
+            Err(Error::LatePacket {
+                timestamp: 100,
+                last: 200,
+            })
+        ));
+        assert_eq!((None, 1, 0), (core.video, core.late, core.len()));
+    }
+}
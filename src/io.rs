@@ -1,158 +1,1240 @@
 #![cfg(feature = "io-std")]
 
+use crate::amf0::Value;
+use crate::interleave::queue::{InterleaverCore, Media, Packet};
+use crate::reader::ReaderCore;
+use crate::recovery::RecoveryState;
+use crate::tag::is_metadata;
+use crate::writer::{Injection, WriterCore};
 use crate::{
-    AudioDataHeader, Error, Header, MetaData, Result, TagHeader, TagType, VideoDataHeader,
+    AudioDataHeader, Decryptor, Error, Header, Interleaving, MetaData, ParseMode, Result,
+    ScriptMessage, TagHeader, VideoDataHeader, Warning,
 };
-use std::convert::TryFrom;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
 
-#[derive(Debug)]
-pub struct Tag<D> {
-    pub header: TagHeader,
-    pub data: TagData<D>,
-}
-
-#[derive(Debug)]
-pub struct AudioData<D> {
-    pub header: AudioDataHeader,
-    pub data: D,
-}
-
-#[derive(Debug)]
-pub struct VideoData<D> {
-    pub header: VideoDataHeader,
-    pub data: D,
-}
-
-#[derive(Debug)]
-pub struct ScriptData<D> {
-    pub data: D,
-}
-
-#[derive(Debug)]
-pub enum TagData<D> {
-    Audio(AudioData<D>),
-    Video(VideoData<D>),
-    ScriptData(ScriptData<D>),
-    Reserved(D),
-}
-
-#[derive(Debug)]
-pub enum Field<D> {
-    PreTagSize(u32),
-    Tag(Tag<D>),
-}
+pub use crate::{AudioData, Field, Recovery, ScriptData, SkippedRange, Tag, TagData, VideoData};
 
 pub struct FlvWriter<W> {
     writer: W,
+    core: WriterCore,
 }
 
 impl<W: Write> FlvWriter<W> {
     pub fn new(writer: W) -> Self {
-        Self { writer }
+        Self {
+            writer,
+            core: WriterCore::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
     }
 
+    /// Writes the file header, padded with zeros up to `header.data_offset`.
+    ///
+    /// The padding is limited to [`Header::MAX_EXTENSION_SIZE`], use
+    /// [`FlvWriter::write_header_with_extension`] to write more.
     pub fn write_header(&mut self, header: Header) -> Result<u64> {
-        let buffer: [u8; 9] = header.into();
-        self.writer.write_all(&buffer)?;
+        let written = self.core.write_header(header)?;
+        self.write_buffer()?;
+        Ok(written)
+    }
 
-        // PreviousTagSize0 is 0u32
-        self.writer.write_all(&[0, 0, 0, 0])?;
+    /// Writes the file header followed by `extension`, `header.data_offset`
+    /// is replaced to point right after it.
+    pub fn write_header_with_extension(&mut self, header: Header, extension: &[u8]) -> Result<u64> {
+        let written = self.core.write_header_with_extension(header, extension)?;
+        self.write_buffer()?;
+        Ok(written)
+    }
 
-        Ok(9 + 4)
+    pub fn write_metadata(&mut self, metadata: &MetaData) -> Result<u64> {
+        let written = self.core.write_metadata(metadata)?;
+        self.write_buffer()?;
+        Ok(written)
     }
 
-    pub fn write_metadata(&mut self, _metadata: MetaData) -> Result<u64> {
-        Ok(0)
+    /// Writes a script tag carrying `message`, such as an onCuePoint whose
+    /// timestamp matches the cue point time.
+    pub fn write_script_message(&mut self, timestamp: i32, message: &ScriptMessage) -> Result<u64> {
+        let written = self.core.write_script_message(timestamp, message)?;
+        self.write_buffer()?;
+        Ok(written)
     }
 
-    fn write_tag(
+    /// Writes a script tag made of the `name` string followed by `values`,
+    /// such as a custom data message.
+    pub fn write_script_tag(
         &mut self,
         timestamp: i32,
-        tag_type: TagType,
-        header: &[u8],
+        name: &str,
+        values: &[Value],
+    ) -> Result<u64> {
+        let written = self.core.write_script_tag(timestamp, name, values)?;
+        self.write_buffer()?;
+        Ok(written)
+    }
+
+    /// Writes `data` as the whole body of a tag of any type, `Reserved`
+    /// included, to pass existing tags through unchanged.
+    ///
+    /// The data size of `tag_header` is replaced by the length of `data`, a
+    /// tag type or reserved bits that don't fit in a tag header fail the write.
+    /// Unfiltered audio and video tags with a zero stream id are accounted
+    /// for in the metadata of [`FlvWriter::finalize`], as with
+    /// [`FlvWriter::write_whole_tag`].
+    pub fn write_raw_tag(&mut self, tag_header: TagHeader, data: &[u8]) -> Result<u64> {
+        let written = self.core.write_raw_tag(tag_header, data)?;
+        self.write_buffer()?;
+        Ok(written)
+    }
+
+    /// Writes a placeholder onMetaData tag to be filled in by
+    /// [`FlvWriter::finalize`] once all tags are written.
+    ///
+    /// The reserved tag has room for every field except `keyframes`.
+    pub fn reserve_metadata(&mut self) -> Result<u64> {
+        let written = self.core.reserve_metadata()?;
+        self.write_buffer()?;
+        Ok(written)
+    }
+
+    pub fn write_video_tag(
+        &mut self,
+        timestamp: i32,
+        header: VideoDataHeader,
         data: &[u8],
     ) -> Result<u64> {
-        let data_size = data.len();
+        let written = self.core.write_video_tag(timestamp, header, data)?;
+        self.write_buffer()?;
+        Ok(written)
+    }
 
-        if data_size > TagHeader::MAX_DATA_SIZE {
-            return Err(Error::DataSize(data_size));
+    pub fn write_audio_tag(
+        &mut self,
+        timestamp: i32,
+        header: AudioDataHeader,
+        data: &[u8],
+    ) -> Result<u64> {
+        let written = self.core.write_audio_tag(timestamp, header, data)?;
+        self.write_buffer()?;
+        Ok(written)
+    }
+
+    /// Writes a tag as read, filtered tags are passed through untouched.
+    fn write_whole_tag<D: AsRef<[u8]>>(&mut self, tag: &Tag<D>) -> Result<u64> {
+        let written = self.core.write_whole_tag(tag)?;
+        self.write_buffer()?;
+        Ok(written)
+    }
+
+    fn write_buffer(&mut self) -> Result<()> {
+        let result = self.writer.write_all(&self.core.buffer);
+        self.core.buffer.clear();
+        Ok(result?)
+    }
+}
+
+impl<W: Write + Seek> FlvWriter<W> {
+    /// Patches the header flags and the metadata reserved by
+    /// [`FlvWriter::reserve_metadata`] to match the tags actually written,
+    /// then returns to the end of the output.
+    pub fn finalize(&mut self) -> Result<MetaData> {
+        let end = self.writer.stream_position()?;
+        let (metadata, patches) = self.core.finalize(end);
+
+        for (offset, bytes) in patches {
+            self.writer.seek(SeekFrom::Start(offset))?;
+            self.writer.write_all(&bytes)?;
         }
 
-        let tag_header = TagHeader {
-            tag_type,
-            data_size: data_size as u32,
-            timestamp,
-        };
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()?;
 
-        let th_data: [u8; TagHeader::SIZE] = tag_header.into();
+        Ok(metadata)
+    }
+}
+
+/// Buffers audio and video tags coming from separate producers and writes
+/// them with non-decreasing timestamps, within the latency window of
+/// [`Interleaving`].
+///
+/// Each track must be pushed in timestamp order. The header and metadata
+/// are written directly with [`InterleavingWriter::get_mut`] before the
+/// first tag.
+pub struct InterleavingWriter<W> {
+    writer: FlvWriter<W>,
+    core: InterleaverCore,
+}
 
-        self.writer.write_all(&th_data)?;
-        self.writer.write_all(header)?;
-        self.writer.write_all(data)?;
+impl<W: Write> InterleavingWriter<W> {
+    pub fn new(writer: FlvWriter<W>, options: Interleaving) -> Self {
+        Self {
+            writer,
+            core: InterleaverCore::new(options),
+        }
+    }
 
-        Ok((TagHeader::SIZE + 1 + data_size) as u64)
+    pub fn get_ref(&self) -> &FlvWriter<W> {
+        &self.writer
     }
 
-    pub fn write_video_tag(
+    pub fn get_mut(&mut self) -> &mut FlvWriter<W> {
+        &mut self.writer
+    }
+
+    /// Writes the buffered tags, then returns the inner writer.
+    pub fn into_inner(mut self) -> Result<FlvWriter<W>> {
+        self.flush()?;
+        Ok(self.writer)
+    }
+
+    /// Tags waiting for the other track
+    pub fn buffered(&self) -> usize {
+        self.core.len()
+    }
+
+    /// Tags older than the last one written when they were pushed
+    pub fn late_tags(&self) -> u64 {
+        self.core.late
+    }
+
+    /// Queues an audio tag, then writes the tags that are ready.
+    pub fn write_audio_tag(
         &mut self,
         timestamp: i32,
-        header: VideoDataHeader,
+        header: AudioDataHeader,
         data: &[u8],
     ) -> Result<u64> {
-        self.write_tag(timestamp, TagType::Video, &[u8::from(header)], data)
+        self.core.push(Packet {
+            timestamp,
+            media: Media::Audio(header),
+            data: data.to_vec(),
+        })?;
+        self.write_ready()
     }
 
-    pub fn write_audio_tag(
+    /// Queues a video tag, then writes the tags that are ready.
+    pub fn write_video_tag(
         &mut self,
         timestamp: i32,
-        header: AudioDataHeader,
+        header: VideoDataHeader,
         data: &[u8],
     ) -> Result<u64> {
-        self.write_tag(timestamp, TagType::Audio, &[u8::from(header)], data)
+        self.core.push(Packet {
+            timestamp,
+            media: Media::Video(header),
+            data: data.to_vec(),
+        })?;
+        self.write_ready()
+    }
+
+    /// Writes every buffered tag, for the end of the stream.
+    pub fn flush(&mut self) -> Result<u64> {
+        let mut written = 0;
+        while let Some(packet) = self.core.pop_any() {
+            written += self.write_packet(packet)?;
+        }
+        Ok(written)
+    }
+
+    fn write_ready(&mut self) -> Result<u64> {
+        let mut written = 0;
+        while let Some(packet) = self.core.pop() {
+            written += self.write_packet(packet)?;
+        }
+        Ok(written)
+    }
+
+    fn write_packet(&mut self, packet: Packet) -> Result<u64> {
+        let Packet {
+            timestamp,
+            media,
+            data,
+        } = packet;
+
+        match media {
+            Media::Audio(header) => self.writer.write_audio_tag(timestamp, header, &data),
+            Media::Video(header) => self.writer.write_video_tag(timestamp, header, &data),
+        }
     }
 }
 
 pub struct FlvReader<R> {
     reader: R,
+    core: ReaderCore,
 }
 
-impl<R: Read + Seek> FlvReader<R> {
+impl<R: Read> FlvReader<R> {
     pub fn new(reader: R) -> Self {
-        Self { reader }
+        Self {
+            reader,
+            core: ReaderCore::new(),
+        }
     }
 
-    pub fn read_header(&mut self) -> Result<Header> {
-        self.reader.seek(SeekFrom::Start(0))?;
+    /// Sets how unknown field values and anomalies such as non-zero stream
+    /// ids are handled, [`ParseMode::Strict`] by default.
+    pub fn mode(mut self, mode: ParseMode) -> Self {
+        self.core.validator.mode = mode;
+        self
+    }
 
-        let mut buffer = [0u8; Header::SIZE];
-        self.reader.read_exact(&mut buffer)?;
+    /// Shorthand for [`ParseMode::Strict`] or [`ParseMode::Lenient`].
+    pub fn strict(self, strict: bool) -> Self {
+        self.mode(if strict {
+            ParseMode::Strict
+        } else {
+            ParseMode::Lenient
+        })
+    }
 
-        Ok(Header::try_from(buffer)?)
+    /// Decrypts filtered tags in [`FlvReader::read_tag`] with `decryptor`.
+    ///
+    /// Without a decryptor, filtered tags are returned untouched with
+    /// [`TagHeader::filter`] set.
+    pub fn with_decryptor<D: Decryptor + Send + 'static>(mut self, decryptor: D) -> Self {
+        self.core.decryptor = Some(Box::new(decryptor));
+        self
     }
 
-    pub fn read_metadata(&mut self) -> Result<MetaData> {
-        unimplemented!()
+    /// Enables the recovery mode.
+    ///
+    /// When a tag header or a PreviousTagSize doesn't check out,
+    /// [`FlvReader::read_tag`] scans forward for the next tag instead of
+    /// failing: a known tag type, a zero stream id and a following
+    /// PreviousTagSize matching the tag. A tag larger than `max_data_size` or
+    /// going back in time from the last tag is only taken if no other tag
+    /// starts within it. The bytes passed over are reported by
+    /// [`FlvReader::skipped_ranges`].
+    pub fn with_recovery(mut self, options: Recovery) -> Self {
+        self.core.recovery = Some(RecoveryState::new(options));
+        self
+    }
+
+    /// Anomalies found so far in lenient mode, up to [`Warning::MAX_KEPT`]
+    /// of them until they are taken
+    pub fn warnings(&self) -> &[Warning] {
+        &self.core.validator.warnings
+    }
+
+    /// Anomalies found in lenient mode that weren't kept as warnings, the
+    /// list being full
+    pub fn dropped_warnings(&self) -> u64 {
+        self.core.validator.dropped_warnings
+    }
+
+    /// Removes the anomalies found so far, to report them as the reading
+    /// goes.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.core.validator.warnings)
+    }
+
+    /// Byte ranges skipped in recovery mode so far
+    pub fn skipped_ranges(&self) -> &[SkippedRange] {
+        self.core.skipped_ranges()
+    }
+
+    /// Offset of the next byte to be read from the start of the file
+    pub fn position(&self) -> u64 {
+        self.core.position()
+    }
+
+    /// Reads the file header and the extra header bytes up to
+    /// `data_offset`, the reader must be at the start of the file.
+    ///
+    /// Nothing is ever seeked while reading, so any forward-only source such
+    /// as stdin, a pipe or a socket can be read.
+    ///
+    /// The header is read at the current position, call [`FlvReader::rewind`]
+    /// first to read it again.
+    pub fn read_header(&mut self) -> Result<Header> {
+        self.core.begin_header();
+        Ok(drive!(self.read_header()))
+    }
+
+    /// Header bytes between [`Header::SIZE`] and `data_offset` of the last
+    /// header read, empty for a standard header.
+    ///
+    /// Only the first [`Header::MAX_EXTENSION_SIZE`] bytes are kept.
+    pub fn header_extension(&self) -> &[u8] {
+        self.core.header_extension()
+    }
+
+    /// Reads a PreviousTagSize field.
+    ///
+    /// In recovery mode, a value that doesn't match the last tag is left in
+    /// the stream to be skipped by the following [`FlvReader::read_tag`].
+    pub fn read_previous_tag_size(&mut self) -> Result<u32> {
+        Ok(drive!(self.read_previous_tag_size()))
     }
 
     pub fn read_tag_header(&mut self) -> Result<TagHeader> {
-        let mut buffer = [0u8; TagHeader::SIZE];
-        self.reader.read_exact(&mut buffer)?;
+        Ok(drive!(self.read_tag_header()))
+    }
 
-        Ok(buffer.into())
+    /// Reads a whole tag, or returns `None` at the end of the file.
+    pub fn read_tag(&mut self) -> Result<Option<Tag<Vec<u8>>>> {
+        Ok(drive!(self.read_tag()))
     }
 
-    pub fn read_video_data_header(&mut self) -> Result<VideoDataHeader> {
-        let mut buffer = [0u8; 1];
-        self.reader.read_exact(&mut buffer)?;
+    /// Reads a whole tag like [`FlvReader::read_tag`], its payload is split
+    /// off the read buffer and shares it with the neighbouring tags, so
+    /// cloning the payload doesn't copy it.
+    #[cfg(feature = "bytes")]
+    pub fn read_tag_bytes(&mut self) -> Result<Option<Tag<bytes::Bytes>>> {
+        Ok(drive!(self.read_tag_bytes()))
+    }
 
-        Ok(VideoDataHeader::try_from(buffer[0])?)
+    /// Skips the remaining `size` bytes of a tag body by discarding them.
+    pub fn skip_tag_data(&mut self, size: u64) -> Result<()> {
+        self.core.begin_skip(size);
+        drive!(self.skip_tag_data());
+        Ok(())
+    }
+
+    pub fn read_video_data_header(&mut self) -> Result<VideoDataHeader> {
+        Ok(drive!(self.read_video_data_header()))
     }
 
     pub fn read_audio_data_header(&mut self) -> Result<AudioDataHeader> {
-        let mut buffer = [0u8; 1];
-        self.reader.read_exact(&mut buffer)?;
+        Ok(drive!(self.read_audio_data_header()))
+    }
+
+    fn fill(&mut self) -> Result<()> {
+        let read = loop {
+            match self.reader.read(self.core.spare()) {
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                result => break result?,
+            }
+        };
+        self.core.filled(read);
+
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> FlvReader<R> {
+    /// Seeks back to the start of the file.
+    pub fn rewind(&mut self) -> Result<()> {
+        self.seek(0)
+    }
+
+    /// Seeks to `offset` from the start of the file, for example to a
+    /// keyframe position from the metadata.
+    pub fn seek(&mut self, offset: u64) -> Result<()> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.core.seek(offset);
+
+        Ok(())
+    }
+
+    /// Reads the onMetaData script tag that precedes the audio and video tags,
+    /// leaving the reader right after it.
+    pub fn read_metadata(&mut self) -> Result<MetaData> {
+        self.rewind()?;
+        self.read_header()?;
+        self.read_previous_tag_size()?;
+
+        while let Some(tag) = self.read_tag()? {
+            match tag.data {
+                TagData::ScriptData(ScriptData { data }) => {
+                    if let Ok(metadata) = MetaData::decode(&data) {
+                        return Ok(metadata);
+                    }
+                }
+                _ => break,
+            }
+            self.read_previous_tag_size()?;
+        }
+
+        Err(Error::MetaDataNotFound)
+    }
+}
+
+/// Copies the FLV file in `reader` to `writer` with an onMetaData tag
+/// computed from the whole stream placed first, which makes files recorded
+/// from live streams seekable.
+///
+/// Existing onMetaData tags are dropped and all other tags are copied as is.
+/// A truncated last tag, as left by an interrupted recording, is discarded.
+pub fn inject_metadata<R, W>(reader: R, writer: W) -> Result<MetaData>
+where
+    R: Read + Seek,
+    W: Write,
+{
+    let mut reader = FlvReader::new(reader);
+    let mut injection = Injection::default();
+
+    let header = reader.read_header()?;
+    loop {
+        let tag = match reader
+            .read_previous_tag_size()
+            .and_then(|_| reader.read_tag())
+        {
+            Ok(Some(tag)) => tag,
+            Ok(None) => break,
+            Err(e) if e.is_eof() || e.is_truncated() => break,
+            Err(e) => return Err(e),
+        };
+        injection.scan(&tag);
+    }
+    let metadata = injection.metadata(&header);
+
+    // second pass, the header extension is kept
+    reader.rewind()?;
+    reader.read_header()?;
+
+    let mut writer = FlvWriter::new(writer);
+    writer.write_header_with_extension(
+        Header {
+            audio_flag: metadata.has_audio,
+            video_flag: metadata.has_video,
+            ..header
+        },
+        reader.header_extension(),
+    )?;
+    writer.write_metadata(&metadata)?;
+
+    for _ in 0..injection.tag_count {
+        reader.read_previous_tag_size()?;
+        match reader.read_tag()? {
+            Some(tag) if !is_metadata(&tag) => writer.write_whole_tag(&tag)?,
+            Some(_) => continue,
+            None => break,
+        };
+    }
+    writer.writer.flush()?;
+
+    Ok(metadata)
+}
+
+/// A writer past the header of a video-only file
+#[cfg(test)]
+fn sample_writer() -> FlvWriter<Vec<u8>> {
+    let mut writer = FlvWriter::new(vec![]);
+    writer
+        .write_header(Header {
+            version: 1,
+            audio_flag: false,
+            video_flag: true,
+            data_offset: Header::SIZE as u32,
+        })
+        .unwrap();
+    writer
+}
+
+/// A video-only file of `count` AVC tags 40ms apart carrying `payload(i)`,
+/// every 10th one a keyframe
+#[cfg(test)]
+fn sample_flv(count: i32, payload: impl Fn(i32) -> Vec<u8>) -> Vec<u8> {
+    use crate::{VideoCodecId, VideoFrameType};
+
+    let mut writer = sample_writer();
+    for i in 0..count {
+        let header = VideoDataHeader {
+            frame_type: if i % 10 == 0 {
+                VideoFrameType::KeyFrame
+            } else {
+                VideoFrameType::InterFrame
+            },
+            codec_id: VideoCodecId::AVC,
+        };
+        writer.write_video_tag(i * 40, header, &payload(i)).unwrap();
+    }
+    writer.into_inner()
+}
+
+#[test]
+fn inject_metadata_keyframes() {
+    use crate::VideoFrameType;
+    use std::io::Cursor;
+
+    let mut input = sample_flv(50, |i| vec![1, 0, 0, 0, i as u8]);
+    // an interrupted recording
+    input.extend_from_slice(&[9, 0, 0, 10]);
+
+    let mut output = vec![];
+    let mut buffered = std::io::BufWriter::new(&mut output);
+    let metadata = inject_metadata(Cursor::new(input), &mut buffered).unwrap();
+    assert!(buffered.buffer().is_empty());
+    drop(buffered);
+    assert!(metadata.has_video && !metadata.has_audio);
+    assert_eq!(output.len() as f64, metadata.filesize);
+    assert_eq!(25.0, metadata.framerate.round());
+
+    let mut reader = FlvReader::new(Cursor::new(&output));
+    assert!(reader.read_header().unwrap().video_flag);
+    assert_eq!(metadata, reader.read_metadata().unwrap());
+
+    for (time, position) in metadata
+        .keyframes
+        .times
+        .iter()
+        .zip(&metadata.keyframes.filepositions)
+    {
+        reader.seek(*position as u64).unwrap();
+        let tag = reader.read_tag().unwrap().unwrap();
+        assert_eq!((*time * 1000.0) as i32, tag.header.timestamp);
+        match tag.data {
+            TagData::Video(video) => assert_eq!(VideoFrameType::KeyFrame, video.header.frame_type),
+            _ => panic!("not a video tag"),
+        }
+    }
+}
+
+#[test]
+fn finalize_patches_metadata() {
+    use crate::{SoundFormat, SoundRate, SoundSize, SoundType};
+    use std::io::Cursor;
+
+    let audio = AudioDataHeader {
+        sound_format: SoundFormat::MP3,
+        sound_rate: SoundRate::R44kHz,
+        sound_size: SoundSize::S16Bit,
+        sound_type: SoundType::Stereo,
+    };
+
+    let mut writer = FlvWriter::new(Cursor::new(vec![]));
+    writer
+        .write_header(Header {
+            version: 1,
+            audio_flag: true,
+            video_flag: true,
+            data_offset: Header::SIZE as u32,
+        })
+        .unwrap();
+    writer.reserve_metadata().unwrap();
+    for i in 0..=100 {
+        writer.write_audio_tag(i * 20, audio, &[0; 124]).unwrap();
+    }
+    let metadata = writer.finalize().unwrap();
+    let output = writer.into_inner().into_inner();
+
+    assert_eq!(2.0, metadata.duration);
+    assert_eq!(output.len() as f64, metadata.filesize);
+
+    let mut reader = FlvReader::new(Cursor::new(&output));
+    let header = reader.read_header().unwrap();
+    assert!(header.audio_flag && !header.video_flag);
+
+    let read = reader.read_metadata().unwrap();
+    assert_eq!(metadata.duration, read.duration);
+    assert_eq!(metadata.filesize, read.filesize);
+    assert_eq!(metadata.audiodatarate, read.audiodatarate);
+    assert!(read.has_audio && !read.has_video);
+}
+
+#[test]
+fn read_forward_only() {
+    let output = sample_flv(3, |i| vec![i as u8; 10]);
+
+    // `&[u8]` implements `Read` but not `Seek`
+    let mut reader = FlvReader::new(&output[..]);
+    reader.read_header().unwrap();
+    reader.read_previous_tag_size().unwrap();
+
+    let tag_header = reader.read_tag_header().unwrap();
+    reader.skip_tag_data(tag_header.data_size as u64).unwrap();
+    reader.read_previous_tag_size().unwrap();
+
+    let tag = reader.read_tag().unwrap().unwrap();
+    assert_eq!(40, tag.header.timestamp);
+    reader.read_previous_tag_size().unwrap();
+
+    let tag_header = reader.read_tag_header().unwrap();
+    assert!(reader
+        .skip_tag_data(tag_header.data_size as u64 + 5)
+        .is_err());
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn read_tags_as_bytes() {
+    let output = sample_flv(3, |i| vec![i as u8; 10]);
+
+    let mut reader = FlvReader::new(&output[..]);
+    reader.read_header().unwrap();
+    let mut payloads = vec![];
+    loop {
+        reader.read_previous_tag_size().unwrap();
+        match reader.read_tag_bytes().unwrap() {
+            Some(Tag {
+                data: TagData::Video(video),
+                ..
+            }) => payloads.push(video.data),
+            Some(tag) => panic!("unexpected tag: {:?}", tag),
+            None => break,
+        }
+    }
+
+    assert_eq!(3, payloads.len());
+    for (i, payload) in payloads.iter().enumerate() {
+        assert_eq!(&[i as u8; 10], &payload[..]);
+    }
+    // the payloads are slices of the same read buffer
+    let distance = TagHeader::SIZE + 1 + 10 + 4;
+    assert_eq!(
+        payloads[0].as_ptr() as usize + distance,
+        payloads[1].as_ptr() as usize
+    );
+}
+
+#[test]
+fn read_extended_header() {
+    use crate::{ParseError, TagType};
+
+    let mut writer = FlvWriter::new(vec![]);
+    writer
+        .write_header_with_extension(
+            Header {
+                version: 1,
+                audio_flag: false,
+                video_flag: false,
+                data_offset: 0,
+            },
+            b"vendor",
+        )
+        .unwrap();
+    writer
+        .write_whole_tag(&Tag {
+            header: TagHeader {
+                reserved: 0,
+                tag_type: TagType::ScriptData,
+                filter: false,
+                data_size: 0,
+                timestamp: 0,
+                stream_id: 0,
+            },
+            data: TagData::ScriptData(ScriptData { data: [1, 2, 3] }),
+        })
+        .unwrap();
+    let output = writer.into_inner();
+
+    let mut reader = FlvReader::new(&output[..]);
+    assert_eq!(15, reader.read_header().unwrap().data_offset);
+    assert_eq!(b"vendor", reader.header_extension());
+    reader.read_previous_tag_size().unwrap();
+    assert_eq!(3, reader.read_tag_header().unwrap().data_size);
+
+    // the padding is not allocated for any data offset
+    let header = Header {
+        version: 1,
+        audio_flag: false,
+        video_flag: false,
+        data_offset: u32::MAX,
+    };
+    match FlvWriter::new(vec![]).write_header(header) {
+        Err(Error::Parse(ParseError::HeaderDataOffset(u32::MAX))) => {}
+        result => panic!("huge padding accepted: {:?}", result),
+    }
+}
+
+#[test]
+fn recover_from_corrupted_tags() {
+    let mut output = sample_flv(10, |_| vec![9; 100]);
+
+    // tags are 116 bytes long, overwrite the middle of the 3rd and 4th ones
+    let corrupted = 13 + 2 * 116 + 50..13 + 3 * 116 + 20;
+    for b in &mut output[corrupted.clone()] {
+        *b = 0xAA;
+    }
+
+    let mut reader = FlvReader::new(&output[..]).with_recovery(Recovery::default());
+    reader.read_header().unwrap();
+
+    let mut timestamps = vec![];
+    loop {
+        reader.read_previous_tag_size().unwrap();
+        match reader.read_tag().unwrap() {
+            Some(tag) => timestamps.push(tag.header.timestamp),
+            None => break,
+        }
+    }
+
+    assert_eq!(vec![0, 40, 160, 200, 240, 280, 320, 360], timestamps);
+    assert_eq!(
+        &[SkippedRange {
+            offset: 13 + 2 * 116,
+            length: 2 * 116,
+        }],
+        reader.skipped_ranges()
+    );
+}
+
+#[test]
+fn recovery_keeps_clean_files() {
+    use crate::{TagType, VideoCodecId, VideoFrameType};
+
+    let header = VideoDataHeader {
+        frame_type: VideoFrameType::InterFrame,
+        codec_id: VideoCodecId::AVC,
+    };
+
+    let mut writer = sample_writer();
+    // an empty tag, a tag over max_data_size and a timestamp going back
+    let empty = TagHeader {
+        reserved: 0,
+        tag_type: TagType::ScriptData,
+        filter: false,
+        data_size: 0,
+        timestamp: 0,
+        stream_id: 0,
+    };
+    writer.write_raw_tag(empty, &[]).unwrap();
+    writer.write_video_tag(0, header, &[9; 100]).unwrap();
+    writer.write_video_tag(3000, header, &[9; 500]).unwrap();
+    writer.write_video_tag(40, header, &[9; 100]).unwrap();
+    writer.write_video_tag(80, header, &[9; 100]).unwrap();
+    let output = writer.into_inner();
+
+    let read = |recovery: Option<Recovery>| {
+        let mut reader = FlvReader::new(&output[..]);
+        if let Some(options) = recovery {
+            reader = reader.with_recovery(options);
+        }
+        reader.read_header().unwrap();
+
+        let mut tags = vec![];
+        loop {
+            reader.read_previous_tag_size().unwrap();
+            match reader.read_tag().unwrap() {
+                Some(tag) => tags.push(tag),
+                None => break,
+            }
+        }
+        (tags, reader.skipped_ranges().to_vec())
+    };
+
+    let expected = read(None);
+    assert_eq!(5, expected.0.len());
+    let recovery = Recovery {
+        max_data_size: 200,
+        timestamp_tolerance: 1000,
+    };
+    assert_eq!(expected, read(Some(recovery)));
+
+    // past a corrupted PreviousTagSize, the large tag is still found
+    let mut corrupted = output.clone();
+    corrupted[140..144].copy_from_slice(&[0xAA; 4]);
+    let mut reader = FlvReader::new(&corrupted[..]).with_recovery(recovery);
+    reader.read_header().unwrap();
+    let mut timestamps = vec![];
+    loop {
+        reader.read_previous_tag_size().unwrap();
+        match reader.read_tag().unwrap() {
+            Some(tag) => timestamps.push(tag.header.timestamp),
+            None => break,
+        }
+    }
+    assert_eq!(vec![0, 3000, 40, 80], timestamps);
+    assert_eq!(
+        &[SkippedRange {
+            offset: 28,
+            length: 116,
+        }],
+        reader.skipped_ranges()
+    );
+}
+
+#[test]
+fn decrypt_filtered_tags() {
+    use crate::{
+        EncryptionFilter, EncryptionTagHeader, FilterParams, SoundFormat, SoundRate, SoundSize,
+        SoundType, TagType,
+    };
+
+    struct Xor;
+
+    impl Decryptor for Xor {
+        fn decrypt(&mut self, filter: &EncryptionFilter, data: &[u8]) -> Result<Vec<u8>> {
+            match filter.params {
+                FilterParams::Encryption { iv } => Ok(data
+                    .iter()
+                    .zip(iv.iter().cycle())
+                    .map(|(d, k)| d ^ k)
+                    .collect()),
+                _ => Err(Error::Decrypt("unsupported filter".into())),
+            }
+        }
+    }
+
+    let audio = AudioDataHeader {
+        sound_format: SoundFormat::AAC,
+        sound_rate: SoundRate::R44kHz,
+        sound_size: SoundSize::S16Bit,
+        sound_type: SoundType::Stereo,
+    };
+
+    // AACPacketType, then the encryption header and the encrypted frame
+    let mut body = vec![1];
+    EncryptionFilter {
+        header: EncryptionTagHeader {
+            num_filters: 1,
+            filter_name: EncryptionTagHeader::ENCRYPTION.to_owned(),
+            length: 16,
+        },
+        params: FilterParams::Encryption { iv: [0x55; 16] },
+    }
+    .write(&mut body)
+    .unwrap();
+    body.extend_from_slice(&[0x55 ^ 1, 0x55 ^ 2, 0x55 ^ 3]);
+
+    let encrypted = Tag {
+        header: TagHeader {
+            reserved: 0,
+            tag_type: TagType::Audio,
+            filter: true,
+            data_size: 0,
+            timestamp: 0,
+            stream_id: 0,
+        },
+        data: TagData::Audio(AudioData {
+            header: audio,
+            data: body,
+        }),
+    };
+
+    let mut writer = FlvWriter::new(vec![]);
+    writer.write_whole_tag(&encrypted).unwrap();
+    let output = writer.into_inner();
+
+    let tag = FlvReader::new(&output[..]).read_tag().unwrap().unwrap();
+    assert!(tag.header.filter);
+
+    let tag = FlvReader::new(&output[..])
+        .with_decryptor(Xor)
+        .read_tag()
+        .unwrap()
+        .unwrap();
+    assert!(!tag.header.filter);
+    match tag.data {
+        TagData::Audio(audio) => assert_eq!(vec![1, 1, 2, 3], audio.data),
+        _ => panic!("not an audio tag"),
+    }
+
+    // the PreviousTagSize fields match the encrypted tags
+    let mut writer = FlvWriter::new(vec![]);
+    for _ in 0..3 {
+        writer.write_whole_tag(&encrypted).unwrap();
+    }
+    let output = writer.into_inner();
+
+    let mut reader = FlvReader::new(&output[..])
+        .with_decryptor(Xor)
+        .with_recovery(Recovery::default());
+    let mut tags = 0;
+    while let Some(tag) = reader.read_tag().unwrap() {
+        assert!(!tag.header.filter);
+        reader.read_previous_tag_size().unwrap();
+        tags += 1;
+    }
+    assert_eq!(3, tags);
+    assert_eq!(&[] as &[SkippedRange], reader.skipped_ranges());
+}
+
+#[test]
+fn preserve_stream_id() {
+    use crate::{ParseError, TagType};
+
+    let tag = Tag {
+        header: TagHeader {
+            reserved: 0,
+            tag_type: TagType::ScriptData,
+            filter: false,
+            data_size: 0,
+            timestamp: 0,
+            stream_id: 7,
+        },
+        data: TagData::ScriptData(ScriptData { data: vec![5] }),
+    };
+
+    let mut writer = FlvWriter::new(vec![]);
+    for _ in 0..Warning::MAX_KEPT + 2 {
+        writer.write_whole_tag(&tag).unwrap();
+    }
+    let output = writer.into_inner();
+
+    match FlvReader::new(&output[..]).read_tag() {
+        Err(e) => assert!(matches!(e.inner(), Error::Parse(ParseError::StreamId(7)))),
+        _ => panic!("non-zero stream id accepted in strict mode"),
+    }
+
+    let mut reader = FlvReader::new(&output[..]).strict(false);
+    while let Some(read) = reader.read_tag().unwrap() {
+        assert_eq!(7, read.header.stream_id);
+        reader.read_previous_tag_size().unwrap();
+    }
+    assert_eq!(Warning::MAX_KEPT, reader.warnings().len());
+    assert_eq!(2, reader.dropped_warnings());
+}
+
+#[test]
+fn locate_errors() {
+    use crate::{FieldKind, Location, ParseError};
+
+    let mut output = sample_flv(3, |i| vec![i as u8; 10]);
+    let tag_size = (TagHeader::SIZE + 1 + 10 + 4) as u64;
+
+    // the file ends cleanly after the last PreviousTagSize
+    let mut reader = FlvReader::new(&output[..]);
+    reader.read_header().unwrap();
+    reader.read_previous_tag_size().unwrap();
+    while reader.read_tag().unwrap().is_some() {
+        reader.read_previous_tag_size().unwrap();
+    }
+    let e = reader.read_previous_tag_size().unwrap_err();
+    assert!(e.is_eof());
+
+    // an invalid video data header in the second tag
+    output[13 + tag_size as usize + TagHeader::SIZE] = 0xf7;
+    // the third tag is truncated
+    output.truncate(output.len() - 8);
+
+    let mut reader = FlvReader::new(&output[..]).mode(ParseMode::Strict);
+    reader.read_header().unwrap();
+    reader.read_previous_tag_size().unwrap();
+    reader.read_tag().unwrap();
+    reader.read_previous_tag_size().unwrap();
+    let e = reader.read_tag().unwrap_err();
+    assert!(matches!(
+        e.inner(),
+        Error::Parse(ParseError::VideoFrameType(0xf))
+    ));
+    assert_eq!(
+        Some(&Location {
+            offset: 13 + tag_size + TagHeader::SIZE as u64,
+            tag_index: Some(1),
+            field: FieldKind::TagData,
+        }),
+        e.location()
+    );
+
+    reader.read_previous_tag_size().unwrap();
+    let e = reader.read_tag().unwrap_err();
+    assert!(e.is_truncated());
+    assert_eq!(Some(2), e.location().unwrap().tag_index);
+    assert_eq!(
+        "input truncated at offset 76 (tag data at offset 76 in tag 2)",
+        e.to_string()
+    );
+}
+
+#[test]
+fn lenient_mode() {
+    use crate::{FieldKind, ParseError, VideoFrameType};
+
+    let mut output = sample_flv(2, |_| vec![0; 10]);
+    // an unknown frame type in the second tag
+    let offset = 13 + 2 * TagHeader::SIZE + 1 + 10 + 4;
+    output[offset] = 0xf7;
+
+    let read_all = |mode| {
+        let mut reader = FlvReader::new(&output[..]).mode(mode);
+        reader.read_header().unwrap();
+        reader.read_previous_tag_size().unwrap();
+        let mut tags = vec![];
+        while let Some(tag) = reader.read_tag().unwrap() {
+            tags.push(tag);
+            reader.read_previous_tag_size().unwrap();
+        }
+        (tags, reader.take_warnings())
+    };
+
+    let (tags, warnings) = read_all(ParseMode::Lenient);
+    match &tags[1].data {
+        TagData::Video(video) => {
+            assert_eq!(VideoFrameType::Unknown(0xf), video.header.frame_type);
+            assert_eq!(0xf7, u8::from(video.header));
+        }
+        data => panic!("unexpected tag data: {:?}", data),
+    }
+    assert_eq!(1, warnings.len());
+    assert_eq!(ParseError::VideoFrameType(0xf), warnings[0].anomaly);
+    assert_eq!(offset as u64, warnings[0].location.offset);
+    assert_eq!(Some(1), warnings[0].location.tag_index);
+    assert_eq!(FieldKind::TagData, warnings[0].location.field);
+
+    let (tags, warnings) = read_all(ParseMode::Raw);
+    assert_eq!(2, tags.len());
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn cue_points() {
+    use crate::{CuePoint, CuePointType, ScriptMessage, TagData};
+
+    let cue_point = CuePoint {
+        name: "ad-break".to_owned(),
+        time: 1.5,
+        cue_type: CuePointType::Event,
+        parameters: vec![("duration".to_owned(), "30".to_owned())],
+    };
+
+    let mut writer = sample_writer();
+    writer
+        .write_script_message(1500, &cue_point.clone().into())
+        .unwrap();
+    let output = writer.into_inner();
+
+    let mut reader = FlvReader::new(&output[..]);
+    reader.read_header().unwrap();
+    reader.read_previous_tag_size().unwrap();
+    let tag = reader.read_tag().unwrap().unwrap();
+    assert_eq!(1500, tag.header.timestamp);
+    match tag.data {
+        TagData::ScriptData(script) => {
+            assert_eq!(
+                ScriptMessage::CuePoint(cue_point),
+                script.message().unwrap()
+            )
+        }
+        data => panic!("unexpected tag data: {:?}", data),
+    }
+}
+
+#[test]
+fn write_script_and_raw_tags() {
+    use crate::amf0;
+    use crate::TagType;
+    use std::io::Cursor;
+
+    let values = vec![Value::Number(1.0), Value::String("one".to_owned())];
+    let reserved = TagHeader {
+        reserved: 0,
+        tag_type: TagType::Reserved(0x1f),
+        filter: false,
+        data_size: 0,
+        timestamp: 40,
+        stream_id: 0,
+    };
+
+    let video = TagHeader {
+        tag_type: TagType::Video,
+        timestamp: 80,
+        ..reserved
+    };
+
+    let mut writer = FlvWriter::new(Cursor::new(vec![]));
+    writer.write_script_tag(20, "onCustom", &values).unwrap();
+    writer.write_raw_tag(reserved, &[1, 2, 3]).unwrap();
+    writer.write_raw_tag(video, &[0x17, 1, 0, 0, 0]).unwrap();
+    // a tag type that doesn't fit in the tag header
+    let wide = TagHeader {
+        tag_type: TagType::Reserved(0x29),
+        ..reserved
+    };
+    assert!(matches!(
+        writer.write_raw_tag(wide, &[]),
+        Err(Error::TagType(0x29))
+    ));
+    let metadata = writer.finalize().unwrap();
+    let output = writer.into_inner().into_inner();
+
+    // the raw video tag is accounted for
+    assert!(metadata.has_video && !metadata.has_audio);
+    assert_eq!(vec![0.08], metadata.keyframes.times);
+
+    let mut reader = FlvReader::new(&output[..]);
+    let script = reader.read_tag().unwrap().unwrap();
+    reader.read_previous_tag_size().unwrap();
+    let raw = reader.read_tag().unwrap().unwrap();
+
+    assert_eq!(20, script.header.timestamp);
+    match script.data {
+        TagData::ScriptData(ScriptData { data }) => {
+            let decoded = amf0::decode(&data).unwrap();
+            assert_eq!(Value::String("onCustom".to_owned()), decoded[0]);
+            assert_eq!(values, decoded[1..]);
+        }
+        data => panic!("unexpected tag data: {:?}", data),
+    }
+
+    assert_eq!(
+        TagHeader {
+            data_size: 3,
+            ..reserved
+        },
+        raw.header
+    );
+    assert_eq!(TagData::Reserved(vec![1, 2, 3]), raw.data);
+}
+
+#[test]
+fn interleave_tracks() {
+    use crate::{
+        LatePolicy, SoundFormat, SoundRate, SoundSize, SoundType, TagType, VideoCodecId,
+        VideoFrameType,
+    };
+
+    let audio = AudioDataHeader {
+        sound_format: SoundFormat::AAC,
+        sound_rate: SoundRate::R44kHz,
+        sound_size: SoundSize::S16Bit,
+        sound_type: SoundType::Stereo,
+    };
+    let video = VideoDataHeader {
+        frame_type: VideoFrameType::KeyFrame,
+        codec_id: VideoCodecId::AVC,
+    };
+
+    let options = Interleaving {
+        latency: 100,
+        late: LatePolicy::Drop,
+    };
+    let mut writer = InterleavingWriter::new(FlvWriter::new(vec![]), options);
+
+    // the video producer runs ahead, its tags wait for the audio
+    for timestamp in [0, 40, 80] {
+        writer.write_video_tag(timestamp, video, &[1]).unwrap();
+    }
+    assert_eq!(3, writer.buffered());
+    for timestamp in [0, 23, 46, 69] {
+        writer.write_audio_tag(timestamp, audio, &[1]).unwrap();
+    }
+    assert_eq!(1, writer.buffered());
+
+    // the audio stops, the video is written once out of the latency window
+    for timestamp in [120, 200] {
+        writer.write_video_tag(timestamp, video, &[1]).unwrap();
+    }
+    assert_eq!(2, writer.buffered());
+    writer.write_audio_tag(10, audio, &[1]).unwrap();
+    assert_eq!(1, writer.late_tags());
+
+    let output = writer.into_inner().unwrap().into_inner();
+    let mut reader = FlvReader::new(&output[..]);
+    let mut tags = vec![];
+    while let Some(tag) = reader.read_tag().unwrap() {
+        reader.read_previous_tag_size().unwrap();
+        tags.push((tag.header.tag_type, tag.header.timestamp));
+    }
+
+    use TagType::{Audio, Video};
+    let expected = [
+        (Video, 0),
+        (Audio, 0),
+        (Audio, 23),
+        (Video, 40),
+        (Audio, 46),
+        (Audio, 69),
+        (Video, 80),
+        (Video, 120),
+        (Video, 200),
+    ];
+    assert_eq!(&expected[..], &tags[..]);
 
-        Ok(AudioDataHeader::try_from(buffer[0])?)
+    let options = Interleaving {
+        late: LatePolicy::Error,
+        ..options
+    };
+    let mut writer = InterleavingWriter::new(FlvWriter::new(vec![]), options);
+    writer.write_audio_tag(0, audio, &[1]).unwrap();
+    writer.write_video_tag(40, video, &[1]).unwrap();
+    writer.write_audio_tag(50, audio, &[1]).unwrap();
+    match writer.write_video_tag(-10, video, &[1]) {
+        Err(Error::LatePacket { timestamp, last }) => assert_eq!((-10, 40), (timestamp, last)),
+        result => panic!("late tag accepted: {:?}", result),
     }
 }
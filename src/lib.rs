@@ -1,14 +1,53 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[macro_use]
+mod cfg;
+#[macro_use]
+mod async_io;
+
 mod error;
+mod normalize;
+mod reader;
+mod slice;
+mod tag;
+mod timestamp;
 mod types;
+mod writer;
 
-pub use crate::error::{Error, ParseError, Result};
+pub use crate::error::{Error, FieldKind, Location, ParseError, ParseMode, Result, Warning};
+pub use crate::normalize::{Repair, RepairPolicy, TimestampNormalizer};
+pub use crate::slice::{FlvSlice, Tags};
+pub use crate::tag::{AudioData, Field, ScriptData, Tag, TagData, VideoData};
+pub use crate::timestamp::{Timestamp, TimestampUnwrapper};
 pub use crate::types::{
-    AudioDataHeader, Header, MetaData, SeekFlag, SoundFormat, SoundRate, SoundSize, SoundType,
-    TagHeader, TagType, VideoCodecId, VideoDataHeader, VideoFrameType,
+    AudioDataHeader, Header, SeekFlag, SoundFormat, SoundRate, SoundSize, SoundType, TagHeader,
+    TagType, VideoCodecId, VideoDataHeader, VideoFrameType,
 };
 
-#[macro_use]
-mod cfg;
+cfg_alloc! {
+    pub mod amf0;
+    pub mod amf3;
+    mod codec;
+    mod demux;
+    mod encryption;
+    mod interleave;
+    mod metadata;
+    mod recovery;
+    mod script;
+
+    pub use crate::demux::{Event, Events, FlvDemuxer};
+    pub use crate::encryption::{Decryptor, EncryptionFilter, EncryptionTagHeader, FilterParams};
+    pub use crate::interleave::{Interleaving, LatePolicy};
+    pub use crate::metadata::{Keyframes, MetaData, MetaDataBuilder};
+    pub use crate::recovery::{Recovery, SkippedRange};
+    pub use crate::script::{
+        Caption, CaptionInfo, CuePoint, CuePointType, FrameInfo, SampleAccess, ScriptMessage,
+        TextData,
+    };
+}
 
 cfg_io_tokio! {
     pub mod tokio;
@@ -17,3 +56,12 @@ cfg_io_tokio! {
 cfg_io_std! {
     pub mod io;
 }
+
+cfg_io_futures! {
+    pub mod futures;
+}
+
+cfg_mmap! {
+    mod mmap;
+    pub use crate::mmap::{MmapFlv, MmapTags, TagEntry};
+}
@@ -0,0 +1,399 @@
+use crate::amf0::{self, Value};
+use crate::codec;
+use crate::error::ParseError;
+use crate::types::{
+    AudioDataHeader, SoundFormat, SoundRate, SoundSize, SoundType, VideoCodecId, VideoDataHeader,
+    VideoFrameType,
+};
+use alloc::borrow::ToOwned;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// Keyframe index stored in onMetaData, used by players for seeking
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Keyframes {
+    /// keyframe timestamps in seconds
+    pub times: Vec<f64>,
+
+    /// byte offsets of the keyframe tags from the start of the file
+    pub filepositions: Vec<f64>,
+}
+
+/// onMetaData
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MetaData {
+    pub duration: f64,
+    pub width: f64,
+    pub height: f64,
+    pub videodatarate: f64,
+    pub framerate: f64,
+    pub videocodecid: f64,
+    pub audiodatarate: f64,
+    pub audiosamplerate: f64,
+    pub audiosamplesize: f64,
+    pub stereo: bool,
+    pub audiocodecid: f64,
+    pub filesize: f64,
+    pub has_audio: bool,
+    pub has_video: bool,
+
+    /// timestamp of the last tag in seconds
+    pub lasttimestamp: f64,
+    pub keyframes: Keyframes,
+}
+
+impl MetaData {
+    /// Name of the script data object carrying the metadata
+    pub const NAME: &'static str = "onMetaData";
+
+    /// Decodes the body of an onMetaData script tag.
+    pub fn decode(mut data: &[u8]) -> Result<Self, ParseError> {
+        match Value::read(&mut data)? {
+            Value::String(name) if name == Self::NAME => {}
+            _ => return Err(ParseError::MetaData),
+        }
+
+        MetaData::try_from(&Value::read(&mut data)?)
+    }
+
+    /// Encodes the body of an onMetaData script tag.
+    ///
+    /// The encoded size only depends on `has_audio`, `has_video` and the
+    /// number of keyframes, not on the other values.
+    pub fn encode(&self) -> Vec<u8> {
+        Self::encode_value(Value::from(self))
+    }
+
+    /// Encodes the body of an onMetaData script tag with all audio and video
+    /// fields and without keyframes, so that the encoded size is constant and
+    /// the tag can be overwritten in place.
+    pub fn encode_fixed(&self) -> Vec<u8> {
+        Self::encode_value(self.to_value(true))
+    }
+
+    fn encode_value(value: Value) -> Vec<u8> {
+        amf0::encode(&[Value::String(Self::NAME.to_owned()), value])
+            .expect("onMetaData property names fit in AMF0 strings")
+    }
+
+    fn to_value(&self, fixed: bool) -> Value {
+        let m = self;
+        let mut properties = vec![
+            ("duration".to_owned(), Value::Number(m.duration)),
+            ("filesize".to_owned(), Value::Number(m.filesize)),
+        ];
+
+        if m.has_video || fixed {
+            properties.extend(vec![
+                ("width".to_owned(), Value::Number(m.width)),
+                ("height".to_owned(), Value::Number(m.height)),
+                ("videodatarate".to_owned(), Value::Number(m.videodatarate)),
+                ("framerate".to_owned(), Value::Number(m.framerate)),
+                ("videocodecid".to_owned(), Value::Number(m.videocodecid)),
+            ]);
+        }
+
+        if m.has_audio || fixed {
+            properties.extend(vec![
+                ("audiodatarate".to_owned(), Value::Number(m.audiodatarate)),
+                (
+                    "audiosamplerate".to_owned(),
+                    Value::Number(m.audiosamplerate),
+                ),
+                (
+                    "audiosamplesize".to_owned(),
+                    Value::Number(m.audiosamplesize),
+                ),
+                ("stereo".to_owned(), Value::Boolean(m.stereo)),
+                ("audiocodecid".to_owned(), Value::Number(m.audiocodecid)),
+            ]);
+        }
+
+        properties.extend(vec![
+            ("hasAudio".to_owned(), Value::Boolean(m.has_audio)),
+            ("hasVideo".to_owned(), Value::Boolean(m.has_video)),
+            ("lasttimestamp".to_owned(), Value::Number(m.lasttimestamp)),
+        ]);
+
+        if !m.keyframes.times.is_empty() && !fixed {
+            let numbers =
+                |v: &[f64]| Value::StrictArray(v.iter().copied().map(Value::Number).collect());
+            properties.push((
+                "keyframes".to_owned(),
+                Value::Object(vec![
+                    ("times".to_owned(), numbers(&m.keyframes.times)),
+                    (
+                        "filepositions".to_owned(),
+                        numbers(&m.keyframes.filepositions),
+                    ),
+                ]),
+            ));
+        }
+
+        Value::EcmaArray(properties)
+    }
+}
+
+impl From<&MetaData> for Value {
+    fn from(m: &MetaData) -> Self {
+        m.to_value(false)
+    }
+}
+
+impl TryFrom<&Value> for MetaData {
+    type Error = ParseError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        if value.properties().is_none() {
+            return Err(ParseError::MetaData);
+        }
+
+        let number = |name| value.get(name).and_then(Value::as_number).unwrap_or(0.0);
+        let numbers = |keyframes: &Value, name| -> Vec<f64> {
+            match keyframes.get(name) {
+                Some(Value::StrictArray(values)) => {
+                    values.iter().filter_map(Value::as_number).collect()
+                }
+                _ => vec![],
+            }
+        };
+
+        let keyframes = value
+            .get("keyframes")
+            .map(|keyframes| Keyframes {
+                times: numbers(keyframes, "times"),
+                filepositions: numbers(keyframes, "filepositions"),
+            })
+            .unwrap_or_default();
+
+        Ok(Self {
+            duration: number("duration"),
+            width: number("width"),
+            height: number("height"),
+            videodatarate: number("videodatarate"),
+            framerate: number("framerate"),
+            videocodecid: number("videocodecid"),
+            audiodatarate: number("audiodatarate"),
+            audiosamplerate: number("audiosamplerate"),
+            audiosamplesize: number("audiosamplesize"),
+            stereo: value
+                .get("stereo")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            audiocodecid: number("audiocodecid"),
+            filesize: number("filesize"),
+            has_audio: value
+                .get("hasAudio")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            has_video: value
+                .get("hasVideo")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            lasttimestamp: number("lasttimestamp"),
+            keyframes,
+        })
+    }
+}
+
+/// Computes [`MetaData`] from the audio and video tags of a stream.
+///
+/// `filesize` is left to the caller, who knows the final layout of the file.
+#[derive(Debug, Clone, Default)]
+pub struct MetaDataBuilder {
+    last_timestamp: i32,
+    audio_bytes: u64,
+    video_bytes: u64,
+    video_frames: u64,
+    audio: Option<AudioDataHeader>,
+    aac_config: Option<(u32, u8)>,
+    video_codec: Option<VideoCodecId>,
+    dimensions: Option<(u32, u32)>,
+    keyframes: Keyframes,
+}
+
+impl MetaDataBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an audio tag, `data` is the payload following the audio data header.
+    pub fn audio_tag(&mut self, timestamp: i32, header: AudioDataHeader, data: &[u8]) {
+        self.last_timestamp = self.last_timestamp.max(timestamp);
+        self.audio_bytes += 1 + data.len() as u64;
+        self.audio = Some(header);
+
+        if header.sound_format == SoundFormat::AAC
+            && data.first() == Some(&codec::AAC_SEQUENCE_HEADER)
+        {
+            self.aac_config = codec::aac_sample_rate(&data[1..]).or(self.aac_config);
+        }
+    }
+
+    /// Adds a video tag, `data` is the payload following the video data header
+    /// and `position` is the offset of the tag in the output file.
+    pub fn video_tag(
+        &mut self,
+        timestamp: i32,
+        header: VideoDataHeader,
+        data: &[u8],
+        position: u64,
+    ) {
+        self.last_timestamp = self.last_timestamp.max(timestamp);
+        self.video_bytes += 1 + data.len() as u64;
+        self.video_codec = Some(header.codec_id);
+
+        if header.frame_type == VideoFrameType::VideoInfoOrCommandFrame {
+            return;
+        }
+
+        if header.codec_id == VideoCodecId::AVC {
+            match data.first() {
+                Some(&codec::AVC_SEQUENCE_HEADER) => {
+                    if let Some(dimensions) = data.get(4..).and_then(codec::avc_dimensions) {
+                        self.dimensions = Some(dimensions);
+                    }
+                    return;
+                }
+                // end of sequence
+                Some(2) => return,
+                _ => {}
+            }
+        }
+
+        self.video_frames += 1;
+
+        if header.frame_type == VideoFrameType::KeyFrame {
+            self.keyframes.times.push(timestamp as f64 / 1000.0);
+            self.keyframes.filepositions.push(position as f64);
+        }
+    }
+
+    pub fn build(&self) -> MetaData {
+        let duration = self.last_timestamp as f64 / 1000.0;
+        let rate = |bytes: u64| {
+            if duration > 0.0 {
+                bytes as f64 * 8.0 / 1000.0 / duration
+            } else {
+                0.0
+            }
+        };
+
+        let mut metadata = MetaData {
+            duration,
+            lasttimestamp: duration,
+            has_audio: self.audio.is_some(),
+            has_video: self.video_codec.is_some(),
+            audiodatarate: rate(self.audio_bytes),
+            videodatarate: rate(self.video_bytes),
+            keyframes: self.keyframes.clone(),
+            ..MetaData::default()
+        };
+
+        if let Some(codec_id) = self.video_codec {
+            metadata.videocodecid = u8::from(codec_id) as f64;
+            if duration > 0.0 && self.video_frames > 1 {
+                // the duration spans the intervals between the frames
+                metadata.framerate = (self.video_frames - 1) as f64 / duration;
+            }
+            if let Some((width, height)) = self.dimensions {
+                metadata.width = width as f64;
+                metadata.height = height as f64;
+            }
+        }
+
+        if let Some(audio) = self.audio {
+            metadata.audiocodecid = (u8::from(audio.sound_format) >> 4) as f64;
+            metadata.audiosamplesize = match audio.sound_size {
+                SoundSize::S8Bit => 8.0,
+                SoundSize::S16Bit => 16.0,
+            };
+            match self.aac_config {
+                Some((sample_rate, channels)) => {
+                    metadata.audiosamplerate = sample_rate as f64;
+                    metadata.stereo = channels >= 2;
+                }
+                None => {
+                    metadata.audiosamplerate = match audio.sound_rate {
+                        SoundRate::R5p5kHz => 5512.0,
+                        SoundRate::R11kHz => 11025.0,
+                        SoundRate::R22kHz => 22050.0,
+                        SoundRate::R44kHz => 44100.0,
+                    };
+                    metadata.stereo = audio.sound_type == SoundType::Stereo;
+                }
+            }
+        }
+
+        metadata
+    }
+}
+
+#[test]
+fn metadata_round_trip() {
+    let audio = AudioDataHeader {
+        sound_format: SoundFormat::AAC,
+        sound_rate: SoundRate::R44kHz,
+        sound_size: SoundSize::S16Bit,
+        sound_type: SoundType::Stereo,
+    };
+    let key = VideoDataHeader {
+        frame_type: VideoFrameType::KeyFrame,
+        codec_id: VideoCodecId::AVC,
+    };
+    let inter = VideoDataHeader {
+        frame_type: VideoFrameType::InterFrame,
+        ..key
+    };
+
+    let mut builder = MetaDataBuilder::new();
+    builder.audio_tag(0, audio, &[0, 0x11, 0x90]); // AAC LC, 48kHz, stereo
+    builder.video_tag(0, key, &[0, 0, 0, 0], 100);
+    for i in 0..100 {
+        let header = if i % 25 == 0 { key } else { inter };
+        builder.video_tag(i * 40, header, &[1, 0, 0, 0, 0], 200 + i as u64);
+        builder.audio_tag(i * 40, audio, &[1; 100]);
+    }
+
+    let metadata = builder.build();
+    assert_eq!(3.96, metadata.duration);
+    assert_eq!(48000.0, metadata.audiosamplerate);
+    assert!(metadata.stereo && metadata.has_audio && metadata.has_video);
+    assert_eq!(vec![0.0, 1.0, 2.0, 3.0], metadata.keyframes.times);
+    assert_eq!(
+        vec![200.0, 225.0, 250.0, 275.0],
+        metadata.keyframes.filepositions
+    );
+
+    assert_eq!(Ok(metadata.clone()), MetaData::decode(&metadata.encode()));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    let audio = AudioDataHeader {
+        sound_format: SoundFormat::Unknown(12),
+        sound_rate: SoundRate::R44kHz,
+        sound_size: SoundSize::S16Bit,
+        sound_type: SoundType::Stereo,
+    };
+    let json = serde_json::to_string(&audio).unwrap();
+    assert_eq!(
+        r#"{"sound_format":{"Unknown":12},"sound_rate":"R44kHz","sound_size":"S16Bit","sound_type":"Stereo"}"#,
+        json
+    );
+    assert_eq!(audio, serde_json::from_str(&json).unwrap());
+
+    let mut builder = MetaDataBuilder::new();
+    builder.audio_tag(1000, audio, &[1; 10]);
+    let metadata = builder.build();
+    let json = serde_json::to_string(&metadata).unwrap();
+    assert_eq!(metadata, serde_json::from_str(&json).unwrap());
+
+    let value = Value::read(&mut &metadata.encode()[..]).unwrap();
+    let json = serde_json::to_string(&value).unwrap();
+    assert_eq!(value, serde_json::from_str(&json).unwrap());
+}
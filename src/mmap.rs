@@ -0,0 +1,222 @@
+//! Random access to a memory-mapped FLV file.
+
+use crate::error::{ParseError, Result};
+use crate::slice::FlvSlice;
+use crate::tag::Tag;
+use crate::types::{Header, TagHeader, TagType};
+use memmap2::Mmap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::ops::Range;
+use std::path::Path;
+
+/// A tag of a [`MmapFlv`], as found in its tag table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TagEntry {
+    /// Offset of the tag header in the file
+    pub offset: u64,
+    pub header: TagHeader,
+
+    /// The tag is a video keyframe
+    pub keyframe: bool,
+}
+
+/// A memory-mapped FLV file with a table of its tags.
+///
+/// The table is built when the file is opened by walking the tag headers,
+/// the bodies are only read when a tag is accessed. Tag `n` is then found
+/// in O(1), and by timestamp in O(log n), without any seek or read call.
+///
+/// A truncated tag at the end of the file, as left by an interrupted
+/// recording, is not part of the table.
+///
+/// The file must not be modified while it is mapped, see [`Mmap::map`].
+pub struct MmapFlv {
+    mmap: Mmap,
+    header: Header,
+    entries: Vec<TagEntry>,
+}
+
+impl MmapFlv {
+    /// Maps the file at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::map(&File::open(path)?)
+    }
+
+    /// Maps `file`.
+    pub fn map(file: &File) -> Result<Self> {
+        // SAFETY: the file is only read, modifying it while it is mapped is
+        // documented as unsupported
+        let mmap = unsafe { Mmap::map(file)? };
+        let header = *FlvSlice::new(&mmap)?.header();
+        let entries = tag_table(&mmap, header.data_offset as usize + 4);
+
+        Ok(Self {
+            mmap,
+            header,
+            entries,
+        })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// The whole file, for example to parse it with [`FlvSlice::tags`].
+    pub fn as_slice(&self) -> FlvSlice<'_> {
+        FlvSlice::with_header(&self.mmap, self.header)
+    }
+
+    /// Number of tags
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The tag table
+    pub fn entries(&self) -> &[TagEntry] {
+        &self.entries
+    }
+
+    /// Parses tag `n`, `None` if there are not so many tags.
+    pub fn tag(&self, n: usize) -> Option<std::result::Result<Tag<&[u8]>, ParseError>> {
+        let entry = self.entries.get(n)?;
+        Some(self.as_slice().tag_at(entry.offset as usize))
+    }
+
+    /// Index of the last tag with a timestamp up to `timestamp`, `None` if
+    /// all the tags are later. The timestamps must not decrease.
+    pub fn index_at(&self, timestamp: i32) -> Option<usize> {
+        self.entries
+            .partition_point(|entry| entry.header.timestamp <= timestamp)
+            .checked_sub(1)
+    }
+
+    /// Index of the last video keyframe with a timestamp up to `timestamp`,
+    /// for example to seek or to extract a thumbnail.
+    pub fn keyframe_at(&self, timestamp: i32) -> Option<usize> {
+        let end = self.index_at(timestamp)? + 1;
+        self.entries[..end].iter().rposition(|entry| entry.keyframe)
+    }
+
+    /// Iterates over the tags, in both directions.
+    pub fn tags(&self) -> MmapTags<'_> {
+        self.range(0..self.len())
+    }
+
+    /// Iterates over the tags in `range`, in both directions.
+    pub fn range(&self, range: Range<usize>) -> MmapTags<'_> {
+        let end = range.end.min(self.len());
+        MmapTags {
+            flv: self,
+            range: range.start.min(end)..end,
+        }
+    }
+}
+
+/// Iterator over the tags of a [`MmapFlv`], see [`MmapFlv::tags`]
+pub struct MmapTags<'a> {
+    flv: &'a MmapFlv,
+    range: Range<usize>,
+}
+
+impl<'a> Iterator for MmapTags<'a> {
+    type Item = std::result::Result<Tag<&'a [u8]>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let n = self.range.next()?;
+        self.flv.tag(n)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for MmapTags<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let n = self.range.next_back()?;
+        self.flv.tag(n)
+    }
+}
+
+impl<'a> ExactSizeIterator for MmapTags<'a> {}
+
+/// Walks the tag headers from `offset` up to the end of `data` or to a
+/// truncated tag.
+fn tag_table(data: &[u8], mut offset: usize) -> Vec<TagEntry> {
+    let mut entries = vec![];
+
+    while let Some(bytes) = data.get(offset..offset + TagHeader::SIZE) {
+        let header = match <[u8; TagHeader::SIZE]>::try_from(bytes) {
+            Ok(bytes) => TagHeader::from(bytes),
+            Err(_) => break,
+        };
+
+        let body = offset + TagHeader::SIZE;
+        let end = body + header.data_size as usize + 4;
+        if end > data.len() {
+            break;
+        }
+
+        // the frame type is the high nibble of the video data header
+        let keyframe = header.tag_type == TagType::Video
+            && header.data_size > 0
+            && !header.filter
+            && data[body] >> 4 == 1;
+
+        entries.push(TagEntry {
+            offset: offset as u64,
+            header,
+            keyframe,
+        });
+        offset = end;
+    }
+
+    entries
+}
+
+#[test]
+fn random_access() {
+    use crate::TagData;
+    use std::io::Write;
+
+    let mut input = b"FLV\x01\x01\x00\x00\x00\x09\x00\x00\x00\x00".to_vec();
+    for i in 0..10u8 {
+        // a keyframe every 4 tags
+        let frame = if i % 4 == 0 { 0x17 } else { 0x27 };
+        input.extend_from_slice(&[9, 0, 0, 2, 0, 0, i * 20, 0, 0, 0, 0, frame, i, 0, 0, 0, 13]);
+    }
+    // a truncated tag
+    input.extend_from_slice(&[9, 0, 0, 2, 0, 1, 144, 0, 0, 0, 0, 0x17]);
+
+    let path = std::env::temp_dir().join(format!("flv-mmap-{}.flv", std::process::id()));
+    File::create(&path).unwrap().write_all(&input).unwrap();
+    let flv = MmapFlv::open(&path).unwrap();
+
+    assert_eq!(10, flv.len());
+    let payload = |tag: Tag<&[u8]>| match tag.data {
+        TagData::Video(video) => video.data[0],
+        data => panic!("unexpected tag data: {:?}", data),
+    };
+    assert_eq!(7, payload(flv.tag(7).unwrap().unwrap()));
+    assert!(flv.tag(10).is_none());
+
+    assert_eq!(Some(3), flv.index_at(70));
+    assert_eq!(None, flv.index_at(-1));
+    assert_eq!(Some(4), flv.keyframe_at(150));
+
+    let reversed = flv
+        .tags()
+        .rev()
+        .map(|tag| payload(tag.unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(vec![9, 8, 7, 6, 5, 4, 3, 2, 1, 0], reversed);
+    assert_eq!(3, flv.range(7..20).len());
+
+    drop(flv);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,257 @@
+//! Repair of the timestamps of a tag stream.
+
+use crate::timestamp::{Timestamp, TimestampUnwrapper};
+use crate::{Tag, TagType};
+
+/// How [`TimestampNormalizer`] fixes a timestamp going backward or jumping
+/// forward by more than [`Repair::max_gap`] within a track
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepairPolicy {
+    /// The tag gets the nearest timestamp within `max_gap` of the previous
+    /// one of its track, and the following tags of the track are shifted
+    /// along.
+    Clamp,
+
+    /// The tag gets the previous timestamp of its track plus the last frame
+    /// duration, and the following tags of the track are shifted along.
+    Extrapolate,
+
+    /// Like `Extrapolate`, but a track jumping after another one takes the
+    /// same shift if that lands within `max_gap` of its previous tag, which
+    /// keeps audio and video in sync when both jump, as after a publisher
+    /// restart. A jump of a single track doesn't shift the others.
+    #[default]
+    Offset,
+}
+
+/// Options of [`TimestampNormalizer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Repair {
+    pub policy: RepairPolicy,
+
+    /// Largest step forward in milliseconds between two tags of a track not
+    /// considered a discontinuity
+    pub max_gap: i32,
+
+    /// Frame duration in milliseconds to extrapolate with, until one is
+    /// measured on the track
+    pub frame_duration: i32,
+}
+
+impl Default for Repair {
+    fn default() -> Self {
+        Self {
+            policy: RepairPolicy::default(),
+            max_gap: 1000,
+            frame_duration: 40,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Track {
+    /// last timestamp given to a tag of the track
+    last: Option<i64>,
+
+    /// last step forward between two tags of the track
+    frame_duration: Option<i64>,
+
+    /// added to the timestamps of the track
+    offset: i64,
+}
+
+/// Rewrites the timestamps of a tag stream so that the first audio or video
+/// tag is at 0 and each track keeps going forward without large gaps.
+///
+/// Timestamps are unwrapped with a [`TimestampUnwrapper`] first. Audio and
+/// video tags are rebased on the same origin, and tags that would still be
+/// negative are moved to 0. Script data and reserved tags get the timestamp
+/// of the tag before them, 0 before the first audio or video tag.
+#[derive(Debug, Clone, Default)]
+pub struct TimestampNormalizer {
+    options: Repair,
+    unwrapper: TimestampUnwrapper,
+
+    /// unwrapped timestamp of the first audio or video tag
+    base: Option<i64>,
+
+    /// offset of the track that jumped last, taken by the other tracks when
+    /// they jump too or start
+    offset: i64,
+    audio: Track,
+    video: Track,
+
+    /// last timestamp given to a tag
+    last: i64,
+}
+
+impl TimestampNormalizer {
+    pub fn new(options: Repair) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+
+    /// The repaired timestamp in milliseconds of the next tag of the stream.
+    pub fn normalize(&mut self, tag_type: TagType, timestamp: Timestamp) -> i64 {
+        let mut track = match tag_type {
+            TagType::Audio => self.audio,
+            TagType::Video => self.video,
+            _ => return self.last,
+        };
+
+        let unwrapped = self.unwrapper.unwrap(timestamp);
+        let base = *self.base.get_or_insert(unwrapped);
+        if track.last.is_none() {
+            track.offset = self.offset;
+        }
+        let rebased = unwrapped - base + track.offset;
+
+        let repaired = match track.last {
+            Some(last) => {
+                let step = rebased - last;
+                let max_gap = self.options.max_gap.into();
+
+                let realigned = rebased - track.offset + self.offset;
+
+                if (0..=max_gap).contains(&step) {
+                    if step > 0 {
+                        track.frame_duration = Some(step);
+                    }
+                    rebased
+                } else if self.options.policy == RepairPolicy::Offset
+                    && (0..=max_gap).contains(&(realigned - last))
+                {
+                    track.offset = self.offset;
+                    realigned
+                } else {
+                    let duration = track.frame_duration;
+                    let extrapolated =
+                        last + duration.unwrap_or(self.options.frame_duration.into());
+
+                    let repaired = match self.options.policy {
+                        RepairPolicy::Clamp => rebased.clamp(last, last + max_gap),
+                        RepairPolicy::Extrapolate | RepairPolicy::Offset => extrapolated,
+                    };
+                    track.offset += repaired - rebased;
+                    if self.options.policy == RepairPolicy::Offset {
+                        self.offset = track.offset;
+                    }
+                    repaired
+                }
+            }
+            None => rebased,
+        };
+
+        let repaired = repaired.max(0);
+        track.last = Some(repaired);
+        self.last = repaired;
+
+        match tag_type {
+            TagType::Audio => self.audio = track,
+            _ => self.video = track,
+        }
+        repaired
+    }
+
+    /// Repairs the timestamp of `tag` in place, wrapping past 32 bits.
+    pub fn normalize_tag<D>(&mut self, tag: &mut Tag<D>) {
+        let header = &mut tag.header;
+        header.timestamp = self.normalize(header.tag_type, header.timestamp.into()) as i32;
+    }
+
+    /// Forgets the previous tags, for a new stream.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.options);
+    }
+}
+
+#[test]
+fn normalize_timestamps() {
+    use TagType::{Audio, ScriptData, Video};
+
+    let run = |policy, tags: &[(TagType, i32)]| {
+        let mut normalizer = TimestampNormalizer::new(Repair {
+            policy,
+            ..Repair::default()
+        });
+        let mut out = [0i64; 8];
+        for (o, &(tag_type, timestamp)) in out.iter_mut().zip(tags) {
+            *o = normalizer.normalize(tag_type, timestamp.into());
+        }
+        out
+    };
+
+    // rebased on the first audio or video tag, negative ones included
+    let rebased = [
+        (ScriptData, 0),
+        (Video, -80),
+        (Audio, -100),
+        (Video, -40),
+        (Audio, -60),
+        (ScriptData, 5000),
+        (Video, 0),
+        (Audio, -20),
+    ];
+    assert_eq!(
+        [0, 0, 0, 40, 20, 20, 80, 60],
+        run(RepairPolicy::Offset, &rebased)
+    );
+
+    // the video jumps back, then forward
+    let video = [
+        (Video, 1000),
+        (Video, 1040),
+        (Video, 500),
+        (Video, 540),
+        (Video, 9000),
+        (Video, 9040),
+        (Video, 9080),
+        (Video, 9120),
+    ];
+    assert_eq!(
+        [0, 40, 40, 80, 1080, 1120, 1160, 1200],
+        run(RepairPolicy::Clamp, &video)
+    );
+    assert_eq!(
+        [0, 40, 80, 120, 160, 200, 240, 280],
+        run(RepairPolicy::Extrapolate, &video)
+    );
+
+    // both tracks restart, only the offset policy keeps them in sync
+    let restart = [
+        (Video, 0),
+        (Audio, 0),
+        (Video, 40),
+        (Audio, 20),
+        (Video, 0),
+        (Audio, 0),
+        (Video, 40),
+        (Audio, 20),
+    ];
+    assert_eq!(
+        [0, 0, 40, 20, 80, 40, 120, 60],
+        run(RepairPolicy::Extrapolate, &restart)
+    );
+    assert_eq!(
+        [0, 0, 40, 20, 80, 80, 120, 100],
+        run(RepairPolicy::Offset, &restart)
+    );
+
+    // only the video jumps, the audio keeps its timestamps
+    let glitch = [
+        (Video, 0),
+        (Audio, 0),
+        (Video, 40),
+        (Audio, 20),
+        (Video, 10040),
+        (Audio, 50),
+        (Video, 10080),
+        (Audio, 70),
+    ];
+    assert_eq!(
+        [0, 0, 40, 20, 80, 50, 120, 70],
+        run(RepairPolicy::Offset, &glitch)
+    );
+}
@@ -0,0 +1,608 @@
+//! The part of the `FlvReader`s shared by every backend: the lookahead
+//! buffer, the [`FlvDemuxer`] and the recovery mode. The backends only move
+//! bytes from their source into [`ReaderCore::spare`].
+#![cfg(any(feature = "io-std", feature = "io-tokio", feature = "io-futures"))]
+
+use crate::demux::{Event, FlvDemuxer};
+use crate::encryption::decrypt_tag;
+use crate::recovery::{Candidate, RecoveryState};
+use crate::{
+    AudioDataHeader, Decryptor, Error, FieldKind, Header, Location, ParseError, ParseMode, Result,
+    SkippedRange, Tag, TagData, TagHeader, TagType, VideoDataHeader, Warning,
+};
+
+/// `None` means that more input is needed before retrying
+pub(crate) type Step<T> = Result<Option<T>>;
+
+/// Bytes read from the source at once
+const CHUNK_SIZE: usize = 8 << 10;
+
+/// With the `bytes` feature, tag bodies are split off the lookahead buffer
+/// and share it.
+#[cfg(feature = "bytes")]
+type Lookahead = bytes::BytesMut;
+#[cfg(not(feature = "bytes"))]
+type Lookahead = Vec<u8>;
+
+/// An event of the demuxer, without the payload
+enum Parsed {
+    Header(Header),
+    PreviousTagSize(u32),
+    TagHeader(TagHeader),
+    Payload,
+    TagEnd,
+}
+
+/// The mode and the warnings of a reader or of the tokio codec, shared so
+/// that every backend accepts the same streams
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Validator {
+    pub(crate) mode: ParseMode,
+    pub(crate) warnings: Vec<Warning>,
+    pub(crate) dropped_warnings: u64,
+}
+
+impl Validator {
+    /// Handles an anomaly of the field at `location` according to the mode:
+    /// it fails the read, is recorded as a warning or is ignored.
+    pub(crate) fn check(
+        &mut self,
+        result: std::result::Result<(), ParseError>,
+        location: Location,
+    ) -> Result<()> {
+        match (result, self.mode) {
+            (Ok(()), _) | (Err(_), ParseMode::Raw) => Ok(()),
+            (Err(e), ParseMode::Strict) => Err(e.into()),
+            (Err(_), ParseMode::Lenient) if self.warnings.len() >= Warning::MAX_KEPT => {
+                self.dropped_warnings += 1;
+                Ok(())
+            }
+            (Err(anomaly), ParseMode::Lenient) => {
+                self.warnings.push(Warning { location, anomaly });
+                Ok(())
+            }
+        }
+    }
+
+    /// Checks the stream id, the only field of a tag header that the
+    /// demuxer leaves to the mode.
+    pub(crate) fn check_tag_header(
+        &mut self,
+        header: &TagHeader,
+        location: Location,
+    ) -> Result<()> {
+        match header.stream_id {
+            0 => Ok(()),
+            stream_id => self.check(Err(ParseError::StreamId(stream_id)), location),
+        }
+    }
+
+    /// Checks the audio or video header fields of a tag body.
+    pub(crate) fn check_tag_data<D>(
+        &mut self,
+        data: &TagData<D>,
+        location: Location,
+    ) -> Result<()> {
+        self.check(data.validate(), location)
+    }
+}
+
+pub(crate) struct ReaderCore {
+    /// `lookahead[start..end]` was read from the source but not consumed yet
+    lookahead: Lookahead,
+    start: usize,
+    end: usize,
+    eof: bool,
+
+    /// offset of the next byte to be consumed
+    position: u64,
+
+    /// the field being read and its offset, for the location of errors
+    field: FieldKind,
+    field_start: u64,
+
+    /// index of the tag of the field being read and of the next tag, unknown
+    /// after a seek
+    tag_index: Option<u64>,
+    next_tag_index: Option<u64>,
+
+    demuxer: FlvDemuxer,
+
+    /// the tag being read by `read_tag`
+    tag: Option<(TagHeader, Vec<u8>)>,
+    skip_remaining: u64,
+    resync_start: Option<u64>,
+
+    /// while resynchronizing at an unlikely tag, the offset of the next
+    /// candidate and the end of the unlikely tag, both from the position
+    resync_fallback: Option<(usize, usize)>,
+
+    pub(crate) recovery: Option<RecoveryState>,
+    pub(crate) decryptor: Option<Box<dyn Decryptor + Send>>,
+    pub(crate) validator: Validator,
+}
+
+impl ReaderCore {
+    pub(crate) fn new() -> Self {
+        Self {
+            lookahead: Lookahead::new(),
+            start: 0,
+            end: 0,
+            eof: false,
+            position: 0,
+            field: FieldKind::Header,
+            field_start: 0,
+            tag_index: None,
+            next_tag_index: Some(0),
+            demuxer: FlvDemuxer::new(),
+            tag: None,
+            skip_remaining: 0,
+            resync_start: None,
+            resync_fallback: None,
+            recovery: None,
+            decryptor: None,
+            validator: Validator::default(),
+        }
+    }
+
+    /// Free space at the end of the lookahead buffer to read into, followed
+    /// by a call to [`ReaderCore::filled`]
+    pub(crate) fn spare(&mut self) -> &mut [u8] {
+        if self.start == self.end {
+            self.start = 0;
+            self.end = 0;
+        } else if self.start > self.lookahead.len() / 2 {
+            self.lookahead.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.start = 0;
+        }
+
+        if self.lookahead.len() < self.end + CHUNK_SIZE {
+            self.lookahead.resize(self.end + CHUNK_SIZE, 0);
+        }
+
+        &mut self.lookahead[self.end..]
+    }
+
+    /// `n` bytes were read into [`ReaderCore::spare`], 0 at the end of the
+    /// source.
+    pub(crate) fn filled(&mut self, n: usize) {
+        self.end += n;
+        self.eof = n == 0;
+    }
+
+    /// The source was moved to `offset`, the start of the file or of a tag.
+    pub(crate) fn seek(&mut self, offset: u64) {
+        self.start = 0;
+        self.end = 0;
+        self.eof = false;
+        self.position = offset;
+        self.field = FieldKind::Header;
+        self.field_start = offset;
+        self.tag_index = None;
+        self.next_tag_index = if offset == 0 { Some(0) } else { None };
+        self.tag = None;
+        self.skip_remaining = 0;
+        self.resync_start = None;
+        self.resync_fallback = None;
+        if let Some(recovery) = &mut self.recovery {
+            recovery.lost = false;
+        }
+
+        match offset {
+            0 => self.demuxer.reset(),
+            _ => self.demuxer.seek_to_tag(),
+        }
+    }
+
+    pub(crate) fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Attaches the location of the field being read to `error`.
+    pub(crate) fn locate(&self, error: Error) -> Error {
+        error.at(self.location())
+    }
+
+    fn location(&self) -> Location {
+        Location {
+            offset: self.field_start,
+            tag_index: self.tag_index,
+            field: self.field,
+        }
+    }
+
+    /// Handles an anomaly of the field being read according to the mode.
+    fn check(&mut self, result: std::result::Result<(), ParseError>) -> Result<()> {
+        let location = self.location();
+        self.validator.check(result, location)
+    }
+
+    pub(crate) fn header_extension(&self) -> &[u8] {
+        self.demuxer.header_extension()
+    }
+
+    pub(crate) fn skipped_ranges(&self) -> &[SkippedRange] {
+        self.recovery
+            .as_ref()
+            .map(|recovery| recovery.skipped.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn begin_header(&mut self) {
+        self.demuxer.reset();
+        self.tag_index = None;
+        self.begin_field(FieldKind::Header);
+    }
+
+    pub(crate) fn read_header(&mut self) -> Step<Header> {
+        loop {
+            if !self.ensure(1)? {
+                return Ok(None);
+            }
+            if let Some(Parsed::Header(header)) = self.next_event(usize::MAX)? {
+                return Ok(Some(header));
+            }
+        }
+    }
+
+    /// In recovery mode, a value that doesn't match the last tag is left
+    /// unconsumed and the next tag is searched from there.
+    pub(crate) fn read_previous_tag_size(&mut self) -> Step<u32> {
+        self.begin_field(FieldKind::PreviousTagSize);
+        if !self.ensure(4)? {
+            return Ok(None);
+        }
+
+        let b = self.buffered();
+        let previous_tag_size = u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+        if let Some(recovery) = &mut self.recovery {
+            if previous_tag_size != recovery.last_tag_size {
+                recovery.lost = true;
+                return Ok(Some(previous_tag_size));
+            }
+        }
+
+        self.demuxer.seek_to_previous_tag_size();
+        match self.next_event(4)? {
+            Some(Parsed::PreviousTagSize(previous_tag_size)) => Ok(Some(previous_tag_size)),
+            _ => unreachable!("4 bytes make a PreviousTagSize"),
+        }
+    }
+
+    pub(crate) fn read_tag_header(&mut self) -> Step<TagHeader> {
+        self.begin_field(FieldKind::TagHeader);
+        if !self.ensure(TagHeader::SIZE)? {
+            return Ok(None);
+        }
+
+        self.demuxer.seek_to_tag();
+        let header = match self.next_event(TagHeader::SIZE)? {
+            Some(Parsed::TagHeader(header)) => header,
+            _ => unreachable!("{} bytes make a tag header", TagHeader::SIZE),
+        };
+
+        let location = self.location();
+        self.validator.check_tag_header(&header, location)?;
+
+        self.begin_field(FieldKind::TagData);
+        Ok(Some(header))
+    }
+
+    /// Reads a whole tag, `Some(None)` at the end of the file.
+    pub(crate) fn read_tag(&mut self) -> Step<Option<Tag<Vec<u8>>>> {
+        let header = match self.begin_tag()? {
+            Some(Some(header)) => header,
+            Some(None) => return Ok(Some(None)),
+            None => return Ok(None),
+        };
+        if let Some((_, data)) = &mut self.tag {
+            data.reserve(header.data_size as usize);
+        }
+
+        loop {
+            match self.next_event(usize::MAX)? {
+                Some(Parsed::TagEnd) => break,
+                Some(_) => {}
+                None if self.eof => {
+                    self.tag = None;
+                    return Err(ParseError::Truncated(self.field_start).into());
+                }
+                None => return Ok(None),
+            }
+        }
+
+        let (header, data) = match self.tag.take() {
+            Some(tag) => tag,
+            None => unreachable!("a tag is being read"),
+        };
+
+        self.end_tag(header, data, TagData::from_body).map(Some)
+    }
+
+    /// Reads a whole tag like [`ReaderCore::read_tag`], its body is split off
+    /// the lookahead buffer without being copied.
+    #[cfg(feature = "bytes")]
+    pub(crate) fn read_tag_bytes(&mut self) -> Step<Option<Tag<bytes::Bytes>>> {
+        let header = match self.begin_tag()? {
+            Some(Some(header)) => header,
+            Some(None) => return Ok(Some(None)),
+            None => return Ok(None),
+        };
+
+        let size = header.data_size as usize;
+        match self.ensure(size) {
+            Ok(true) => {}
+            Ok(false) => return Ok(None),
+            Err(e) => {
+                self.tag = None;
+                return Err(e);
+            }
+        }
+
+        let _ = self.lookahead.split_to(self.start);
+        self.end -= self.start;
+        self.start = 0;
+        let body = self.lookahead.split_to(size).freeze();
+        self.end -= size;
+        self.position += size as u64;
+
+        // only the TagEnd event is left, the body was consumed above
+        while let (_, Some(Event::PayloadChunk(_))) = self.demuxer.feed(&body)? {}
+        self.tag = None;
+
+        self.end_tag(header, body, |tag_type, body| {
+            TagData::from_bytes(tag_type, body).map_err(Into::into)
+        })
+        .map(Some)
+    }
+
+    /// The header of the tag being read, read first after a resync if needed,
+    /// `Some(None)` at the end of the file.
+    fn begin_tag(&mut self) -> Step<Option<TagHeader>> {
+        if let Some((header, _)) = &self.tag {
+            return Ok(Some(Some(*header)));
+        }
+
+        if self.buffered().is_empty() {
+            return Ok(if self.eof { Some(None) } else { None });
+        }
+
+        if let Some(recovery) = &mut self.recovery {
+            if !recovery.lost {
+                match recovery.check(&self.lookahead[self.start..self.end], self.eof) {
+                    Candidate::Plausible | Candidate::Unlikely(_) => {}
+                    Candidate::NeedMore(_) => return Ok(None),
+                    Candidate::Implausible | Candidate::End => recovery.lost = true,
+                }
+            }
+        }
+
+        if self.recovery.as_ref().is_some_and(|recovery| recovery.lost) {
+            match self.resync()? {
+                Some(true) => {}
+                Some(false) => return Ok(Some(None)),
+                None => return Ok(None),
+            }
+        }
+
+        let header = match self.read_tag_header()? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        self.tag = Some((header, vec![]));
+
+        Ok(Some(Some(header)))
+    }
+
+    /// Decrypts the body of a tag that was read and splits it with
+    /// `from_body`.
+    fn end_tag<D: AsRef<[u8]> + From<Vec<u8>>>(
+        &mut self,
+        header: TagHeader,
+        data: D,
+        from_body: impl FnOnce(TagType, D) -> Result<TagData<D>>,
+    ) -> Result<Option<Tag<D>>> {
+        // the following PreviousTagSize matches the tag as it was read
+        if let Some(recovery) = &mut self.recovery {
+            recovery.tag_read(&header);
+        }
+
+        let (header, data) = match &mut self.decryptor {
+            Some(decryptor) if header.filter => {
+                let (header, data) = decrypt_tag(decryptor.as_mut(), header, data.as_ref())?;
+                (header, D::from(data))
+            }
+            _ => (header, data),
+        };
+
+        let data = from_body(header.tag_type, data)?;
+        let location = self.location();
+        self.validator.check_tag_data(&data, location)?;
+
+        Ok(Some(Tag { header, data }))
+    }
+
+    pub(crate) fn begin_skip(&mut self, size: u64) {
+        self.skip_remaining = size;
+        self.begin_field(FieldKind::TagData);
+    }
+
+    pub(crate) fn skip_tag_data(&mut self) -> Step<()> {
+        while self.skip_remaining > 0 {
+            if !self.ensure(1)? {
+                return Ok(None);
+            }
+
+            let before = self.position;
+            let limit = self.skip_remaining.min(usize::MAX as u64) as usize;
+            self.next_event(limit)?;
+            self.skip_remaining -= self.position - before;
+        }
+
+        Ok(Some(()))
+    }
+
+    pub(crate) fn read_video_data_header(&mut self) -> Step<VideoDataHeader> {
+        self.begin_field(FieldKind::VideoDataHeader);
+        match self.read_byte()? {
+            Some(b) => {
+                let header = VideoDataHeader::from_byte(b);
+                self.check(header.validate())?;
+                Ok(Some(header))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn read_audio_data_header(&mut self) -> Step<AudioDataHeader> {
+        self.begin_field(FieldKind::AudioDataHeader);
+        match self.read_byte()? {
+            Some(b) => {
+                let header = AudioDataHeader::from_byte(b);
+                self.check(header.validate())?;
+                Ok(Some(header))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn read_byte(&mut self) -> Step<u8> {
+        if !self.ensure(1)? {
+            return Ok(None);
+        }
+
+        let b = self.buffered()[0];
+        self.next_event(1)?;
+
+        Ok(Some(b))
+    }
+
+    /// Starts reading `field` at the current position, a tag header starts a
+    /// new tag. Retrying a step doesn't start the field again.
+    fn begin_field(&mut self, field: FieldKind) {
+        if self.field == field && self.field_start == self.position {
+            return;
+        }
+
+        if field == FieldKind::TagHeader {
+            self.tag_index = self.next_tag_index;
+            self.next_tag_index = self.next_tag_index.map(|index| index + 1);
+        }
+        self.field = field;
+        self.field_start = self.position;
+    }
+
+    /// Moves to the next plausible tag, `Some(false)` if there is none
+    /// before the end of the file. An unlikely tag is taken if no plausible
+    /// one starts before its end.
+    fn resync(&mut self) -> Step<bool> {
+        let start = *self.resync_start.get_or_insert(self.position);
+
+        let found = loop {
+            let recovery = match &self.recovery {
+                Some(recovery) => recovery,
+                None => break true,
+            };
+
+            if let Some((offset, end)) = self.resync_fallback {
+                if offset >= end || (self.eof && offset >= self.buffered().len()) {
+                    break true;
+                }
+                let candidate = match self.buffered().get(offset..) {
+                    Some(bytes) => recovery.check(bytes, self.eof),
+                    None => Candidate::NeedMore(1),
+                };
+                match candidate {
+                    Candidate::Plausible => {
+                        self.consume(offset);
+                        break true;
+                    }
+                    Candidate::Unlikely(_) | Candidate::Implausible => {
+                        self.resync_fallback = Some((offset + 1, end));
+                    }
+                    Candidate::NeedMore(_) => return Ok(None),
+                    Candidate::End => break true,
+                }
+                continue;
+            }
+
+            match recovery.check(self.buffered(), self.eof) {
+                Candidate::Plausible => break true,
+                Candidate::Unlikely(end) => self.resync_fallback = Some((1, end)),
+                Candidate::Implausible => self.consume(1),
+                Candidate::NeedMore(_) => return Ok(None),
+                Candidate::End => {
+                    let rest = self.buffered().len();
+                    self.consume(rest);
+                    break false;
+                }
+            }
+        };
+
+        self.resync_start = None;
+        self.resync_fallback = None;
+        if let Some(recovery) = &mut self.recovery {
+            recovery.skip(start, self.position);
+            recovery.lost = false;
+        }
+
+        Ok(Some(found))
+    }
+
+    /// Feeds up to `limit` buffered bytes to the demuxer, payload chunks are
+    /// appended to the tag being read.
+    fn next_event(&mut self, limit: usize) -> Result<Option<Parsed>> {
+        let end = self.end.min(self.start.saturating_add(limit));
+        let (consumed, event) = self.demuxer.feed(&self.lookahead[self.start..end])?;
+
+        let parsed = match event {
+            None => None,
+            Some(Event::Header(header)) => Some(Parsed::Header(header)),
+            Some(Event::PreviousTagSize(size)) => Some(Parsed::PreviousTagSize(size)),
+            Some(Event::TagHeader(header)) => Some(Parsed::TagHeader(header)),
+            Some(Event::PayloadChunk(chunk)) => {
+                if let Some((_, data)) = &mut self.tag {
+                    data.extend_from_slice(chunk);
+                }
+                Some(Parsed::Payload)
+            }
+            Some(Event::TagEnd) => Some(Parsed::TagEnd),
+        };
+
+        self.consume(consumed);
+        Ok(parsed)
+    }
+
+    fn buffered(&self) -> &[u8] {
+        &self.lookahead[self.start..self.end]
+    }
+
+    /// Whether `n` bytes are buffered, failing if the source ended before,
+    /// with [`Error::Eof`] if no byte of the field was read.
+    fn ensure(&self, n: usize) -> Result<bool> {
+        if self.buffered().len() >= n {
+            Ok(true)
+        } else if !self.eof {
+            Ok(false)
+        } else if self.buffered().is_empty() && self.at_boundary() {
+            Err(Error::Eof)
+        } else {
+            Err(ParseError::Truncated(self.field_start).into())
+        }
+    }
+
+    /// Whether no byte of the current field, outside of a tag body, was read
+    fn at_boundary(&self) -> bool {
+        let boundary = matches!(
+            self.field,
+            FieldKind::Header | FieldKind::PreviousTagSize | FieldKind::TagHeader
+        );
+        boundary && self.position == self.field_start
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.start += n;
+        self.position += n as u64;
+    }
+}
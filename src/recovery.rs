@@ -0,0 +1,146 @@
+#[cfg(any(feature = "io-std", feature = "io-tokio", feature = "io-futures"))]
+use crate::types::{TagHeader, TagType};
+
+/// Options of the recovery mode, see `FlvReader::with_recovery`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recovery {
+    /// Largest tag body considered likely, a larger tag found while
+    /// resynchronizing is only taken if no likely tag starts within it
+    pub max_data_size: u32,
+
+    /// How far in milliseconds a timestamp may go back from the last tag
+    /// and still be considered monotonic, like `max_data_size`
+    pub timestamp_tolerance: i32,
+}
+
+impl Default for Recovery {
+    fn default() -> Self {
+        Self {
+            max_data_size: 8 << 20,
+            timestamp_tolerance: 1000,
+        }
+    }
+}
+
+/// A range of corrupted bytes skipped in recovery mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SkippedRange {
+    /// offset from the start of the file
+    pub offset: u64,
+    pub length: u64,
+}
+
+#[cfg(any(feature = "io-std", feature = "io-tokio", feature = "io-futures"))]
+/// Outcome of [`RecoveryState::check`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Candidate {
+    Plausible,
+
+    /// a tag with an unlikely size or timestamp, followed by a matching
+    /// PreviousTagSize ending this many bytes further
+    Unlikely(usize),
+    Implausible,
+
+    /// at least this many bytes are needed to decide
+    NeedMore(usize),
+
+    /// not enough bytes are left for a tag header
+    End,
+}
+
+#[cfg(any(feature = "io-std", feature = "io-tokio", feature = "io-futures"))]
+#[derive(Debug)]
+pub(crate) struct RecoveryState {
+    pub(crate) options: Recovery,
+    pub(crate) last_tag_size: u32,
+    pub(crate) last_timestamp: Option<i32>,
+    pub(crate) skipped: Vec<SkippedRange>,
+
+    /// a tag or PreviousTagSize didn't check out, the next tag is searched
+    pub(crate) lost: bool,
+}
+
+#[cfg(any(feature = "io-std", feature = "io-tokio", feature = "io-futures"))]
+impl RecoveryState {
+    pub(crate) fn new(options: Recovery) -> Self {
+        Self {
+            options,
+            last_tag_size: 0,
+            last_timestamp: None,
+            skipped: vec![],
+            lost: false,
+        }
+    }
+
+    /// Records a tag that was read successfully.
+    pub(crate) fn tag_read(&mut self, header: &TagHeader) {
+        self.last_tag_size = TagHeader::SIZE as u32 + header.data_size;
+        self.last_timestamp = Some(header.timestamp);
+    }
+
+    /// Records the bytes from `start` to `end` as skipped, if any.
+    pub(crate) fn skip(&mut self, start: u64, end: u64) {
+        if end > start {
+            self.skipped.push(SkippedRange {
+                offset: start,
+                length: end - start,
+            });
+        }
+    }
+
+    /// Checks whether a tag starts at the front of `bytes`, the bytes
+    /// buffered at the current position; `eof` tells that no more follow.
+    ///
+    /// The tag header fields and the following PreviousTagSize must be
+    /// valid, the data size and timestamp only make the tag unlikely.
+    pub(crate) fn check(&self, bytes: &[u8], eof: bool) -> Candidate {
+        if bytes.len() < TagHeader::SIZE {
+            return if eof {
+                Candidate::End
+            } else {
+                Candidate::NeedMore(TagHeader::SIZE)
+            };
+        }
+
+        let mut buffer = [0u8; TagHeader::SIZE];
+        buffer.copy_from_slice(&bytes[..TagHeader::SIZE]);
+        let header = TagHeader::from(buffer);
+        let options = self.options;
+
+        if header.reserved != 0 {
+            return Candidate::Implausible;
+        }
+        if let TagType::Reserved(_) = header.tag_type {
+            return Candidate::Implausible;
+        }
+        if header.stream_id != 0 {
+            return Candidate::Implausible;
+        }
+
+        // the PreviousTagSize following the tag, unless the file ends there
+        let tag_size = TagHeader::SIZE + header.data_size as usize;
+        let complete = match bytes.len() {
+            n if n >= tag_size + 4 => {
+                let trailer = &bytes[tag_size..tag_size + 4];
+                u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]])
+                    == tag_size as u32
+            }
+            _ if !eof => return Candidate::NeedMore(tag_size + 4),
+            n => n == tag_size,
+        };
+        if !complete {
+            return Candidate::Implausible;
+        }
+
+        let unlikely = header.data_size > options.max_data_size
+            || header.timestamp < 0
+            || self.last_timestamp.is_some_and(|last| {
+                header.timestamp < last.saturating_sub(options.timestamp_tolerance)
+            });
+        if unlikely {
+            Candidate::Unlikely(tag_size + 4)
+        } else {
+            Candidate::Plausible
+        }
+    }
+}
@@ -0,0 +1,479 @@
+//! The common script data messages besides onMetaData.
+
+use crate::amf0::{self, Properties, Value};
+use crate::error::{Error, ParseError};
+use crate::metadata::MetaData;
+use crate::tag::ScriptData;
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// A script data message, decoded from the body of a script tag: its name
+/// followed by its values
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ScriptMessage {
+    MetaData(MetaData),
+    CuePoint(CuePoint),
+    TextData(TextData),
+    CaptionInfo(CaptionInfo),
+    Caption(Caption),
+    FrameInfo(FrameInfo),
+    SampleAccess(SampleAccess),
+    Other { name: String, values: Vec<Value> },
+}
+
+impl ScriptMessage {
+    /// Decodes the body of a script tag, messages with an unknown name
+    /// being [`ScriptMessage::Other`].
+    pub fn decode(data: &[u8]) -> Result<Self, ParseError> {
+        let mut values = amf0::decode(data)?.into_iter();
+        let name = match values.next() {
+            Some(Value::String(name)) => name,
+            _ => return Err(ParseError::ScriptData),
+        };
+        let value = values.as_slice().first().unwrap_or(&Value::Null);
+
+        Ok(match name.as_str() {
+            MetaData::NAME => ScriptMessage::MetaData(MetaData::try_from(value)?),
+            CuePoint::NAME => ScriptMessage::CuePoint(CuePoint::try_from(value)?),
+            TextData::NAME => ScriptMessage::TextData(TextData::try_from(value)?),
+            CaptionInfo::NAME => ScriptMessage::CaptionInfo(CaptionInfo::try_from(value)?),
+            Caption::NAME => ScriptMessage::Caption(Caption::try_from(value)?),
+            FrameInfo::NAME => ScriptMessage::FrameInfo(FrameInfo::try_from(value)?),
+            SampleAccess::NAME => {
+                let mut flag = || values.next().and_then(|v| v.as_bool()).unwrap_or(false);
+                ScriptMessage::SampleAccess(SampleAccess {
+                    audio: flag(),
+                    video: flag(),
+                })
+            }
+            _ => ScriptMessage::Other {
+                name,
+                values: values.collect(),
+            },
+        })
+    }
+
+    /// Encodes the body of a script tag, see [`Value::write`].
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let value = match self {
+            ScriptMessage::MetaData(m) => Value::from(m),
+            ScriptMessage::CuePoint(c) => Value::from(c),
+            ScriptMessage::TextData(t) => Value::from(t),
+            ScriptMessage::CaptionInfo(c) => Value::from(c),
+            ScriptMessage::Caption(c) => Value::from(c),
+            ScriptMessage::FrameInfo(f) => Value::from(f),
+            ScriptMessage::SampleAccess(s) => {
+                let values = [Value::Boolean(s.audio), Value::Boolean(s.video)];
+                return encode_message(self.name(), &values);
+            }
+            ScriptMessage::Other { name, values } => return encode_message(name, values),
+        };
+
+        encode_message(self.name(), &[value])
+    }
+
+    /// Name of the message, such as `onCuePoint`
+    pub fn name(&self) -> &str {
+        match self {
+            ScriptMessage::MetaData(_) => MetaData::NAME,
+            ScriptMessage::CuePoint(_) => CuePoint::NAME,
+            ScriptMessage::TextData(_) => TextData::NAME,
+            ScriptMessage::CaptionInfo(_) => CaptionInfo::NAME,
+            ScriptMessage::Caption(_) => Caption::NAME,
+            ScriptMessage::FrameInfo(_) => FrameInfo::NAME,
+            ScriptMessage::SampleAccess(_) => SampleAccess::NAME,
+            ScriptMessage::Other { name, .. } => name,
+        }
+    }
+}
+
+impl<D: AsRef<[u8]>> ScriptData<D> {
+    /// Decodes the message carried by the tag, see [`ScriptMessage::decode`].
+    pub fn message(&self) -> Result<ScriptMessage, ParseError> {
+        ScriptMessage::decode(self.data.as_ref())
+    }
+}
+
+macro_rules! impl_from_message {
+    ($($message:ident)*) => {
+        $(
+            impl From<$message> for ScriptMessage {
+                fn from(message: $message) -> Self {
+                    ScriptMessage::$message(message)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_message! {
+    MetaData CuePoint TextData CaptionInfo Caption FrameInfo SampleAccess
+}
+
+/// Kind of a cue point
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CuePointType {
+    /// triggers an action, such as an ad insertion
+    Event,
+    /// a seek target
+    Navigation,
+    Other(String),
+}
+
+impl CuePointType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            CuePointType::Event => "event",
+            CuePointType::Navigation => "navigation",
+            CuePointType::Other(s) => s,
+        }
+    }
+}
+
+impl From<&str> for CuePointType {
+    fn from(s: &str) -> Self {
+        match s {
+            "event" => CuePointType::Event,
+            "navigation" => CuePointType::Navigation,
+            s => CuePointType::Other(s.to_owned()),
+        }
+    }
+}
+
+/// onCuePoint, usually written with a tag timestamp matching `time`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CuePoint {
+    pub name: String,
+
+    /// time of the cue point in seconds
+    pub time: f64,
+    pub cue_type: CuePointType,
+
+    /// string parameters, in wire order
+    pub parameters: Vec<(String, String)>,
+}
+
+impl CuePoint {
+    pub const NAME: &'static str = "onCuePoint";
+}
+
+impl From<&CuePoint> for Value {
+    fn from(c: &CuePoint) -> Self {
+        let parameters = c
+            .parameters
+            .iter()
+            .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+            .collect();
+
+        Value::Object(vec![
+            ("name".to_owned(), Value::String(c.name.clone())),
+            ("time".to_owned(), Value::Number(c.time)),
+            (
+                "type".to_owned(),
+                Value::String(c.cue_type.as_str().to_owned()),
+            ),
+            ("parameters".to_owned(), Value::Object(parameters)),
+        ])
+    }
+}
+
+impl TryFrom<&Value> for CuePoint {
+    type Error = ParseError;
+
+    /// Parameters which are not strings are skipped.
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let parameters = value
+            .get("parameters")
+            .and_then(Value::properties)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_owned())))
+            .collect();
+
+        Ok(Self {
+            name: required(value, "name")?,
+            time: value
+                .get("time")
+                .and_then(Value::as_number)
+                .ok_or(ParseError::ScriptData)?,
+            cue_type: string(value, "type").as_str().into(),
+            parameters,
+        })
+    }
+}
+
+/// onTextData, a subtitle of a timed text track
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextData {
+    pub text: String,
+    pub track_id: f64,
+
+    /// ISO 639-2 language code, empty if absent
+    pub language: String,
+}
+
+impl TextData {
+    pub const NAME: &'static str = "onTextData";
+}
+
+impl From<&TextData> for Value {
+    fn from(t: &TextData) -> Self {
+        let mut properties = vec![
+            ("text".to_owned(), Value::String(t.text.clone())),
+            ("trackid".to_owned(), Value::Number(t.track_id)),
+        ];
+        optional(&mut properties, "language", &t.language);
+        Value::Object(properties)
+    }
+}
+
+impl TryFrom<&Value> for TextData {
+    type Error = ParseError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        Ok(Self {
+            text: required(value, "text")?,
+            track_id: value
+                .get("trackid")
+                .and_then(Value::as_number)
+                .unwrap_or(0.0),
+            language: string(value, "language"),
+        })
+    }
+}
+
+/// onCaptionInfo, CEA-608/708 closed captions
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CaptionInfo {
+    /// caption standard, such as `708`
+    pub caption_type: String,
+
+    /// base64 encoded caption data
+    pub data: String,
+}
+
+impl CaptionInfo {
+    pub const NAME: &'static str = "onCaptionInfo";
+}
+
+impl From<&CaptionInfo> for Value {
+    fn from(c: &CaptionInfo) -> Self {
+        Value::Object(vec![
+            ("type".to_owned(), Value::String(c.caption_type.clone())),
+            ("data".to_owned(), Value::String(c.data.clone())),
+        ])
+    }
+}
+
+impl TryFrom<&Value> for CaptionInfo {
+    type Error = ParseError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        Ok(Self {
+            caption_type: string(value, "type"),
+            data: required(value, "data")?,
+        })
+    }
+}
+
+/// onCaption, a caption as text
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Caption {
+    pub text: String,
+
+    /// ISO 639-2 language code, empty if absent
+    pub language: String,
+}
+
+impl Caption {
+    pub const NAME: &'static str = "onCaption";
+}
+
+impl From<&Caption> for Value {
+    fn from(c: &Caption) -> Self {
+        let mut properties = vec![("text".to_owned(), Value::String(c.text.clone()))];
+        optional(&mut properties, "language", &c.language);
+        Value::Object(properties)
+    }
+}
+
+impl TryFrom<&Value> for Caption {
+    type Error = ParseError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        Ok(Self {
+            text: required(value, "text")?,
+            language: string(value, "language"),
+        })
+    }
+}
+
+/// onFI, the wall clock time or timecode of a frame, as written by encoders
+/// such as Flash Media Live Encoder
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameInfo {
+    /// system date as `dd-mm-yyyy`
+    pub system_date: Option<String>,
+
+    /// system time as `hh:mm:ss.sss`
+    pub system_time: Option<String>,
+
+    /// SMPTE timecode as `hh:mm:ss:ff`
+    pub timecode: Option<String>,
+}
+
+impl FrameInfo {
+    pub const NAME: &'static str = "onFI";
+}
+
+impl From<&FrameInfo> for Value {
+    fn from(f: &FrameInfo) -> Self {
+        let fields = [
+            ("sd", &f.system_date),
+            ("st", &f.system_time),
+            ("tc", &f.timecode),
+        ];
+        let properties = fields
+            .iter()
+            .filter_map(|(key, value)| {
+                let value = value.as_ref()?;
+                Some(((*key).to_owned(), Value::String(value.clone())))
+            })
+            .collect();
+
+        Value::EcmaArray(properties)
+    }
+}
+
+impl TryFrom<&Value> for FrameInfo {
+    type Error = ParseError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        if value.properties().is_none() {
+            return Err(ParseError::ScriptData);
+        }
+
+        let field = |name| {
+            value
+                .get(name)
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned)
+        };
+        Ok(Self {
+            system_date: field("sd"),
+            system_time: field("st"),
+            timecode: field("tc"),
+        })
+    }
+}
+
+/// |RtmpSampleAccess, whether the player may access the raw audio and video
+/// data, for example to draw it in a bitmap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SampleAccess {
+    pub audio: bool,
+    pub video: bool,
+}
+
+impl SampleAccess {
+    pub const NAME: &'static str = "|RtmpSampleAccess";
+}
+
+/// Encodes `name` followed by `values`.
+pub(crate) fn encode_message(name: &str, values: &[Value]) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![];
+    Value::String(name.to_owned()).write(&mut buf)?;
+    for value in values {
+        value.write(&mut buf)?;
+    }
+    Ok(buf)
+}
+
+/// A string property of `value`, failing if it is missing
+fn required(value: &Value, name: &str) -> Result<String, ParseError> {
+    let s = value.get(name).and_then(Value::as_str);
+    s.map(ToOwned::to_owned).ok_or(ParseError::ScriptData)
+}
+
+/// A string property of `value`, empty if it is missing
+fn string(value: &Value, name: &str) -> String {
+    let s = value.get(name).and_then(Value::as_str);
+    s.unwrap_or_default().to_owned()
+}
+
+/// Adds a string property unless it is empty.
+fn optional(properties: &mut Properties, name: &str, value: &str) {
+    if !value.is_empty() {
+        properties.push((name.to_owned(), Value::String(value.to_owned())));
+    }
+}
+
+#[test]
+fn script_messages() {
+    let cue_point = CuePoint {
+        name: "ad-break".to_owned(),
+        time: 12.5,
+        cue_type: CuePointType::Event,
+        parameters: vec![("duration".to_owned(), "30".to_owned())],
+    };
+    let messages = vec![
+        ScriptMessage::from(cue_point.clone()),
+        ScriptMessage::TextData(TextData {
+            text: "Hello".to_owned(),
+            track_id: 1.0,
+            language: "eng".to_owned(),
+        }),
+        ScriptMessage::CaptionInfo(CaptionInfo {
+            caption_type: "708".to_owned(),
+            data: "/AAA".to_owned(),
+        }),
+        ScriptMessage::Caption(Caption {
+            text: "Hello".to_owned(),
+            language: String::new(),
+        }),
+        ScriptMessage::FrameInfo(FrameInfo {
+            system_date: Some("18-10-2026".to_owned()),
+            system_time: Some("14:50:37.120".to_owned()),
+            timecode: None,
+        }),
+        ScriptMessage::SampleAccess(SampleAccess {
+            audio: false,
+            video: true,
+        }),
+        ScriptMessage::MetaData(MetaData::default()),
+        ScriptMessage::Other {
+            name: "onUserData".to_owned(),
+            values: vec![Value::Number(1.0)],
+        },
+    ];
+    for message in messages {
+        assert_eq!(
+            Ok(&message),
+            ScriptMessage::decode(&message.encode().unwrap()).as_ref()
+        );
+    }
+
+    let script = ScriptData {
+        data: ScriptMessage::from(cue_point).encode().unwrap(),
+    };
+    assert_eq!("onCuePoint", script.message().unwrap().name());
+
+    // a cue point without a name
+    let data = amf0::encode(&[
+        Value::String(CuePoint::NAME.to_owned()),
+        Value::Object(vec![("time".to_owned(), Value::Number(1.0))]),
+    ])
+    .unwrap();
+    assert_eq!(Err(ParseError::ScriptData), ScriptMessage::decode(&data));
+}
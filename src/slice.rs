@@ -0,0 +1,179 @@
+//! Zero-copy parsing of an FLV file held in memory.
+
+use crate::error::ParseError;
+use crate::tag::{Tag, TagData};
+use crate::types::{Header, TagHeader};
+use core::convert::TryFrom;
+
+/// An FLV file parsed in place from a byte slice.
+///
+/// Tags are yielded as `Tag<&'a [u8]>`, their payloads borrowing from the
+/// input, so nothing is copied or allocated. The input can be a whole file
+/// read in memory or a memory-mapped one.
+///
+/// ```
+/// use flv::{FlvSlice, TagData};
+///
+/// # fn main() -> Result<(), flv::ParseError> {
+/// let mut file = b"FLV\x01\x01\x00\x00\x00\x09\x00\x00\x00\x00".to_vec();
+/// file.extend_from_slice(&[9, 0, 0, 3, 0, 0, 40, 0, 0, 0, 0, 0x17, 1, 2, 0, 0, 0, 14]);
+///
+/// let flv = FlvSlice::new(&file)?;
+/// for tag in flv.tags() {
+///     if let TagData::Video(video) = tag?.data {
+///         assert_eq!(&[1, 2], video.data);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct FlvSlice<'a> {
+    data: &'a [u8],
+    header: Header,
+}
+
+impl<'a> FlvSlice<'a> {
+    /// Parses the file header at the start of `data`.
+    pub fn new(data: &'a [u8]) -> Result<Self, ParseError> {
+        let mut buffer = [0u8; Header::SIZE];
+        buffer.copy_from_slice(field(data, 0, Header::SIZE)?);
+        let header = Header::try_from(buffer)?;
+        field(data, 0, header.data_offset as usize)?;
+
+        Ok(Self { data, header })
+    }
+
+    /// An input whose header was already parsed
+    #[cfg(feature = "mmap")]
+    pub(crate) fn with_header(data: &'a [u8], header: Header) -> Self {
+        Self { data, header }
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Header bytes between [`Header::SIZE`] and `data_offset`, empty for a
+    /// standard header.
+    pub fn header_extension(&self) -> &'a [u8] {
+        &self.data[Header::SIZE..self.header.data_offset as usize]
+    }
+
+    /// The whole input
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Iterates over the tags, from the first one.
+    pub fn tags(&self) -> Tags<'a> {
+        self.tags_from(self.header.data_offset as usize + 4)
+    }
+
+    /// Iterates over the tags, from the one starting at `offset`, for
+    /// example a keyframe position from the metadata.
+    pub fn tags_from(&self, offset: usize) -> Tags<'a> {
+        Tags {
+            data: self.data,
+            offset,
+            failed: false,
+        }
+    }
+
+    /// Parses the tag starting at `offset`.
+    pub fn tag_at(&self, offset: usize) -> Result<Tag<&'a [u8]>, ParseError> {
+        tag_at(self.data, offset)
+    }
+}
+
+/// Iterator over the tags of a [`FlvSlice`], PreviousTagSize fields are
+/// skipped.
+///
+/// The iterator ends after the last tag or after an error.
+#[derive(Debug, Clone)]
+pub struct Tags<'a> {
+    data: &'a [u8],
+    offset: usize,
+    failed: bool,
+}
+
+impl<'a> Tags<'a> {
+    /// Offset of the next tag
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<'a> Iterator for Tags<'a> {
+    type Item = Result<Tag<&'a [u8]>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.offset >= self.data.len() {
+            return None;
+        }
+
+        let result = tag_at(self.data, self.offset).and_then(|tag| {
+            let end = self.offset + TagHeader::SIZE + tag.header.data_size as usize;
+            field(self.data, end, 4)?;
+            self.offset = end + 4;
+            Ok(tag)
+        });
+
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+fn tag_at(data: &[u8], offset: usize) -> Result<Tag<&[u8]>, ParseError> {
+    let mut buffer = [0u8; TagHeader::SIZE];
+    buffer.copy_from_slice(field(data, offset, TagHeader::SIZE)?);
+    let header = TagHeader::from(buffer);
+
+    let body = field(data, offset + TagHeader::SIZE, header.data_size as usize)?;
+    let data = TagData::from_slice(header.tag_type, body)?;
+    data.validate()?;
+
+    Ok(Tag { header, data })
+}
+
+/// `size` bytes of `data` at `offset`
+fn field(data: &[u8], offset: usize, size: usize) -> Result<&[u8], ParseError> {
+    data.get(offset..offset.saturating_add(size))
+        .ok_or(ParseError::Truncated(offset as u64))
+}
+
+#[test]
+fn parse_slice() {
+    use crate::{ScriptData, TagType};
+
+    let mut input = b"FLV\x01\x01\x00\x00\x00\x0bxy\x00\x00\x00\x00".to_vec();
+    // a video tag of 3 bytes and a script tag of 2 bytes
+    input.extend_from_slice(&[9, 0, 0, 3, 0, 0, 40, 0, 0, 0, 0, 0x17, 1, 2, 0, 0, 0, 14]);
+    input.extend_from_slice(&[18, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 5, 6, 0, 0, 0, 13]);
+
+    let flv = FlvSlice::new(&input).unwrap();
+    assert_eq!(b"xy", flv.header_extension());
+
+    let tags = flv.tags().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(2, tags.len());
+    match &tags[0].data {
+        TagData::Video(video) => assert_eq!(&[1, 2], video.data),
+        data => panic!("unexpected tag data: {:?}", data),
+    }
+    assert_eq!(TagType::ScriptData, tags[1].header.tag_type);
+
+    // random access to the script tag
+    let tag = flv.tag_at(15 + 18).unwrap();
+    assert_eq!(
+        TagData::ScriptData(ScriptData {
+            data: &[5u8, 6][..]
+        }),
+        tag.data
+    );
+
+    let truncated = FlvSlice::new(&input[..input.len() - 3]).unwrap();
+    let mut tags = truncated.tags();
+    assert!(tags.next().unwrap().is_ok());
+    assert_eq!(Some(Err(ParseError::Truncated(33 + 13))), tags.next());
+    assert!(tags.next().is_none());
+}
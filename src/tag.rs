@@ -0,0 +1,150 @@
+use crate::error::ParseError;
+#[cfg(any(feature = "io-std", feature = "io-tokio", feature = "io-futures"))]
+use crate::error::Result;
+use crate::types::{AudioDataHeader, TagHeader, TagType, VideoDataHeader};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tag<D> {
+    pub header: TagHeader,
+    pub data: TagData<D>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AudioData<D> {
+    pub header: AudioDataHeader,
+    pub data: D,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VideoData<D> {
+    pub header: VideoDataHeader,
+    pub data: D,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScriptData<D> {
+    pub data: D,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TagData<D> {
+    Audio(AudioData<D>),
+    Video(VideoData<D>),
+    ScriptData(ScriptData<D>),
+    Reserved(D),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Field<D> {
+    PreTagSize(u32),
+    Tag(Tag<D>),
+}
+
+impl<D> TagData<D> {
+    /// Fails if the audio or video data header has an unknown value.
+    pub fn validate(&self) -> core::result::Result<(), ParseError> {
+        match self {
+            TagData::Audio(audio) => audio.header.validate(),
+            TagData::Video(video) => video.header.validate(),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(any(feature = "io-std", feature = "io-tokio", feature = "io-futures"))]
+impl TagData<Vec<u8>> {
+    /// Splits the body of a tag of type `tag_type` into the audio or video
+    /// data header and the payload.
+    /// Unknown header values are kept, see [`TagData::validate`].
+    pub(crate) fn from_body(tag_type: TagType, mut body: Vec<u8>) -> Result<Self> {
+        let first_byte = |body: &[u8]| body.first().copied().ok_or(ParseError::TagData);
+
+        Ok(match tag_type {
+            TagType::Audio => TagData::Audio(AudioData {
+                header: AudioDataHeader::from_byte(first_byte(&body)?),
+                data: body.split_off(1),
+            }),
+            TagType::Video => TagData::Video(VideoData {
+                header: VideoDataHeader::from_byte(first_byte(&body)?),
+                data: body.split_off(1),
+            }),
+            TagType::ScriptData => TagData::ScriptData(ScriptData { data: body }),
+            TagType::Reserved(_) => TagData::Reserved(body),
+        })
+    }
+}
+
+impl<'a> TagData<&'a [u8]> {
+    /// Splits the body of a tag of type `tag_type` into the audio or video
+    /// data header and the payload, borrowing from `body`.
+    /// Unknown header values are kept, see [`TagData::validate`].
+    pub(crate) fn from_slice(
+        tag_type: TagType,
+        body: &'a [u8],
+    ) -> core::result::Result<Self, ParseError> {
+        let split = |body: &'a [u8]| body.split_first().ok_or(ParseError::TagData);
+
+        Ok(match tag_type {
+            TagType::Audio => {
+                let (&first, data) = split(body)?;
+                TagData::Audio(AudioData {
+                    header: AudioDataHeader::from_byte(first),
+                    data,
+                })
+            }
+            TagType::Video => {
+                let (&first, data) = split(body)?;
+                TagData::Video(VideoData {
+                    header: VideoDataHeader::from_byte(first),
+                    data,
+                })
+            }
+            TagType::ScriptData => TagData::ScriptData(ScriptData { data: body }),
+            TagType::Reserved(_) => TagData::Reserved(body),
+        })
+    }
+}
+
+#[cfg(all(
+    feature = "bytes",
+    any(feature = "io-std", feature = "io-tokio", feature = "io-futures")
+))]
+impl TagData<bytes::Bytes> {
+    /// Splits the body of a tag of type `tag_type` into the audio or video
+    /// data header and the payload, sharing the buffer of `body`.
+    /// Unknown header values are kept, see [`TagData::validate`].
+    pub(crate) fn from_bytes(
+        tag_type: TagType,
+        mut body: bytes::Bytes,
+    ) -> core::result::Result<Self, ParseError> {
+        let first_byte = |body: &[u8]| body.first().copied().ok_or(ParseError::TagData);
+
+        Ok(match tag_type {
+            TagType::Audio => TagData::Audio(AudioData {
+                header: AudioDataHeader::from_byte(first_byte(&body)?),
+                data: body.split_off(1),
+            }),
+            TagType::Video => TagData::Video(VideoData {
+                header: VideoDataHeader::from_byte(first_byte(&body)?),
+                data: body.split_off(1),
+            }),
+            TagType::ScriptData => TagData::ScriptData(ScriptData { data: body }),
+            TagType::Reserved(_) => TagData::Reserved(body),
+        })
+    }
+}
+
+#[cfg(any(feature = "io-std", feature = "io-tokio", feature = "io-futures"))]
+/// Whether `tag` is an onMetaData script tag
+pub(crate) fn is_metadata<D: AsRef<[u8]>>(tag: &Tag<D>) -> bool {
+    match &tag.data {
+        TagData::ScriptData(script) => crate::MetaData::decode(script.data.as_ref()).is_ok(),
+        _ => false,
+    }
+}
@@ -0,0 +1,232 @@
+//! Tag timestamps and their extension past the 32 bits of the tag header.
+
+use core::convert::TryFrom;
+use core::fmt;
+use core::time::Duration;
+
+/// A tag timestamp in milliseconds, as stored in [`TagHeader::timestamp`]:
+/// 24 bits plus an extension byte holding the upper 8 bits.
+///
+/// [`TagHeader::timestamp`]: crate::TagHeader::timestamp
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Timestamp(i32);
+
+impl Timestamp {
+    pub const ZERO: Timestamp = Timestamp(0);
+    pub const MIN: Timestamp = Timestamp(i32::MIN);
+    pub const MAX: Timestamp = Timestamp(i32::MAX);
+
+    pub const fn from_millis(millis: i32) -> Self {
+        Timestamp(millis)
+    }
+
+    pub const fn as_millis(self) -> i32 {
+        self.0
+    }
+
+    /// The lower 24 bits, the only ones some encoders write
+    pub const fn lower(self) -> u32 {
+        self.0 as u32 & 0x00FF_FFFF
+    }
+
+    /// The upper 8 bits, stored in the extension byte
+    pub const fn extended(self) -> u8 {
+        (self.0 as u32 >> 24) as u8
+    }
+
+    pub fn checked_add(self, millis: i32) -> Option<Self> {
+        self.0.checked_add(millis).map(Timestamp)
+    }
+
+    pub fn checked_sub(self, millis: i32) -> Option<Self> {
+        self.0.checked_sub(millis).map(Timestamp)
+    }
+
+    /// Milliseconds from `earlier` to `self`, `None` on overflow
+    pub fn checked_since(self, earlier: Timestamp) -> Option<i32> {
+        self.0.checked_sub(earlier.0)
+    }
+
+    pub fn checked_add_duration(self, duration: Duration) -> Option<Self> {
+        let millis = i32::try_from(duration.as_millis()).ok()?;
+        self.checked_add(millis)
+    }
+
+    pub fn checked_sub_duration(self, duration: Duration) -> Option<Self> {
+        let millis = i32::try_from(duration.as_millis()).ok()?;
+        self.checked_sub(millis)
+    }
+
+    /// The time since zero, `None` for a negative timestamp
+    pub fn to_duration(self) -> Option<Duration> {
+        u64::try_from(self.0).ok().map(Duration::from_millis)
+    }
+
+    /// `duration` truncated to milliseconds, `None` if it doesn't fit in
+    /// 32 bits
+    pub fn from_duration(duration: Duration) -> Option<Self> {
+        i32::try_from(duration.as_millis()).ok().map(Timestamp)
+    }
+}
+
+impl From<i32> for Timestamp {
+    fn from(millis: i32) -> Self {
+        Timestamp(millis)
+    }
+}
+
+impl From<Timestamp> for i32 {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.0
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}ms", self.0)
+    }
+}
+
+/// Turns the timestamps of a stream into 64-bit timestamps that keep
+/// increasing when the tag timestamps wrap around.
+///
+/// A timestamp more than half the range below the previous one is a
+/// rollover: of 24 bits when both fit in 24 bits, the encoder ignoring the
+/// extension byte, of 32 bits otherwise. A timestamp more than half the
+/// range above the previous one is a late tag from before the last rollover,
+/// or a negative timestamp. Smaller backward jumps, such as between
+/// interleaved audio and video, are kept as they are.
+#[derive(Debug, Clone, Default)]
+pub struct TimestampUnwrapper {
+    /// previous timestamp, as the 32 bits of the tag header
+    last: Option<u32>,
+
+    /// added to the timestamps since the last rollover
+    offset: i64,
+}
+
+impl TimestampUnwrapper {
+    const RANGE_24: i64 = 1 << 24;
+    const RANGE_32: i64 = 1 << 32;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The 64-bit timestamp in milliseconds of the next tag of the stream.
+    pub fn unwrap(&mut self, timestamp: Timestamp) -> i64 {
+        let current = timestamp.as_millis() as u32 as i64;
+        let last = match self.last {
+            Some(last) => last as i64,
+            None => {
+                if timestamp.as_millis() < 0 {
+                    self.offset = -Self::RANGE_32;
+                }
+                self.last = Some(current as u32);
+                return self.offset + current;
+            }
+        };
+
+        let range = if last < Self::RANGE_24 && current < Self::RANGE_24 {
+            Self::RANGE_24
+        } else {
+            Self::RANGE_32
+        };
+
+        if last - current > range / 2 {
+            self.offset += range;
+        } else if current - last > range / 2 {
+            // a late tag, the rollover is not undone for the next ones
+            return self.offset - range + current;
+        }
+
+        self.last = Some(current as u32);
+        self.offset + current
+    }
+
+    /// Like [`TimestampUnwrapper::unwrap`], as a duration, `None` for a
+    /// negative timestamp.
+    pub fn unwrap_duration(&mut self, timestamp: Timestamp) -> Option<Duration> {
+        u64::try_from(self.unwrap(timestamp))
+            .ok()
+            .map(Duration::from_millis)
+    }
+
+    /// Forgets the previous timestamps, for a new stream.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[test]
+fn timestamp_arithmetic() {
+    let timestamp = Timestamp::from_millis(0x0123_4567);
+    assert_eq!(0x23_4567, timestamp.lower());
+    assert_eq!(0x01, timestamp.extended());
+
+    assert_eq!(
+        Some(Timestamp::from(1000)),
+        Timestamp::ZERO.checked_add(1000)
+    );
+    assert_eq!(None, Timestamp::MAX.checked_add(1));
+    assert_eq!(None, Timestamp::MIN.checked_sub(1));
+    assert_eq!(
+        Some(-500),
+        Timestamp::from(500).checked_since(Timestamp::from(1000))
+    );
+
+    let duration = Duration::from_millis(1500);
+    assert_eq!(Some(duration), Timestamp::from(1500).to_duration());
+    assert_eq!(None, Timestamp::from(-1).to_duration());
+    assert_eq!(
+        Some(Timestamp::from(1500)),
+        Timestamp::from_duration(duration)
+    );
+    assert_eq!(None, Timestamp::from_duration(Duration::from_secs(1 << 32)));
+    assert_eq!(
+        Some(Timestamp::from(2500)),
+        Timestamp::from(1000).checked_add_duration(duration)
+    );
+}
+
+#[test]
+fn unwrap_rollovers() {
+    let unwrap_all = |timestamps: &[u32]| {
+        let mut unwrapper = TimestampUnwrapper::new();
+        let mut out = [0i64; 5];
+        for (o, &t) in out.iter_mut().zip(timestamps) {
+            *o = unwrapper.unwrap(Timestamp::from(t as i32));
+        }
+        out
+    };
+
+    // the extension byte is ignored
+    let wrapped = unwrap_all(&[0xFF_FFC0, 0xFF_FFE0, 0x10, 0xFF_FFF0, 0x30]);
+    assert_eq!(
+        [0xFF_FFC0, 0xFF_FFE0, 0x100_0010, 0xFF_FFF0, 0x100_0030],
+        wrapped
+    );
+
+    // the extension byte is written, and crossing 24 bits is no rollover
+    let extended = unwrap_all(&[0xFF_FFF0, 0x100_0010, 0x7FFF_FFF0, 0xFFFF_FFF0, 0x10]);
+    assert_eq!(
+        [
+            0xFF_FFF0,
+            0x100_0010,
+            0x7FFF_FFF0,
+            0xFFFF_FFF0,
+            0x1_0000_0010
+        ],
+        extended
+    );
+
+    // interleaving jitter and negative timestamps are kept
+    assert_eq!([100, 90, 120, 0, 0], unwrap_all(&[100, 90, 120]));
+    let negative = unwrap_all(&[-40i32 as u32, -20i32 as u32, 0, 100, -10i32 as u32]);
+    assert_eq!([-40, -20, 0, 100, -10], negative);
+}
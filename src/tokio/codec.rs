@@ -0,0 +1,498 @@
+use crate::reader::Validator;
+use crate::{
+    Error, Event, FieldKind, FlvDemuxer, Header, Location, ParseError, ParseMode, ScriptData, Tag,
+    TagData, TagHeader, Warning,
+};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::convert::TryFrom;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// A codec for [`FramedRead`](tokio_util::codec::FramedRead) and
+/// [`FramedWrite`](tokio_util::codec::FramedWrite).
+///
+/// The decoder parses the file header once, keeping it in
+/// [`FlvCodec::header`], then yields the tags with their payloads as `Bytes`.
+/// PreviousTagSize fields are skipped. The parsing is done by a
+/// [`FlvDemuxer`] fed with whole fields, so that each tag body is split off
+/// the read buffer without being copied.
+///
+/// The encoder writes a [`Header`] followed by PreviousTagSize0, or a [`Tag`]
+/// followed by its PreviousTagSize, the data size being computed from the
+/// payload.
+///
+/// Anomalies such as unknown audio or video header values and non-zero stream
+/// ids are handled according to the [`ParseMode`], like the readers do.
+///
+/// Decoding errors carry their [`Location`] in the stream.
+#[derive(Debug, Clone)]
+pub struct FlvCodec {
+    demuxer: FlvDemuxer,
+    header: Option<Header>,
+
+    /// header of the tag whose body is awaited
+    tag_header: Option<TagHeader>,
+    max_data_size: usize,
+    max_header_extension: usize,
+    validator: Validator,
+
+    /// bytes and tag headers decoded so far
+    position: u64,
+    tag_count: u64,
+}
+
+impl FlvCodec {
+    pub fn new() -> Self {
+        Self::with_mode(ParseMode::default())
+    }
+
+    /// A codec decoding with `mode`, [`FlvCodec::new`] uses
+    /// [`ParseMode::Strict`].
+    pub fn with_mode(mode: ParseMode) -> Self {
+        Self {
+            demuxer: FlvDemuxer::new(),
+            header: None,
+            tag_header: None,
+            max_data_size: TagHeader::MAX_DATA_SIZE,
+            max_header_extension: Header::MAX_EXTENSION_SIZE,
+            validator: Validator {
+                mode,
+                ..Validator::default()
+            },
+            position: 0,
+            tag_count: 0,
+        }
+    }
+
+    /// Limits the data size of the tags to decode or encode, bigger ones fail
+    /// with [`Error::DataSize`] before their data is buffered.
+    ///
+    /// The limit can't exceed [`TagHeader::MAX_DATA_SIZE`].
+    pub fn with_max_data_size(mut self, max_data_size: usize) -> Self {
+        self.max_data_size = max_data_size.min(TagHeader::MAX_DATA_SIZE);
+        self
+    }
+
+    /// Limits the bytes between [`Header::SIZE`] and `data_offset` of the
+    /// header to decode or encode, bigger extensions fail with
+    /// [`ParseError::HeaderDataOffset`] before they are buffered.
+    ///
+    /// Defaults to [`Header::MAX_EXTENSION_SIZE`].
+    pub fn with_max_header_extension(mut self, max_header_extension: usize) -> Self {
+        self.demuxer = self.demuxer.with_max_header_extension(max_header_extension);
+        self.max_header_extension = max_header_extension;
+        self
+    }
+
+    /// The file header, once decoded
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
+    /// Header bytes between [`Header::SIZE`] and `data_offset` of the
+    /// decoded header, empty for a standard header.
+    pub fn header_extension(&self) -> &[u8] {
+        self.demuxer.header_extension()
+    }
+
+    /// Anomalies found so far in lenient mode, up to [`Warning::MAX_KEPT`]
+    /// of them until they are taken
+    pub fn warnings(&self) -> &[Warning] {
+        &self.validator.warnings
+    }
+
+    /// Anomalies found in lenient mode that weren't kept as warnings, the
+    /// list being full
+    pub fn dropped_warnings(&self) -> u64 {
+        self.validator.dropped_warnings
+    }
+
+    /// Removes the anomalies found so far, to report them as the decoding
+    /// goes.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.validator.warnings)
+    }
+
+    /// Splits the tag body off `src` once it is complete.
+    fn decode_body(
+        &mut self,
+        header: TagHeader,
+        src: &mut BytesMut,
+    ) -> Result<Option<Tag<Bytes>>, Error> {
+        let data_size = header.data_size as usize;
+        if src.len() < data_size {
+            src.reserve(data_size - src.len());
+            return Ok(None);
+        }
+
+        let data = src.split_to(data_size).freeze();
+        self.consume_body(&data)?;
+        self.tag_header = None;
+
+        let location = self.location(FieldKind::TagData);
+        let data =
+            TagData::from_bytes(header.tag_type, data).map_err(|e| Error::from(e).at(location))?;
+        self.validator
+            .check_tag_data(&data, location)
+            .map_err(|e| e.at(location))?;
+        self.position += data_size as u64;
+
+        Ok(Some(Tag { header, data }))
+    }
+
+    /// Feeds the whole body of the current tag to the demuxer, up to its
+    /// TagEnd event.
+    fn consume_body(&mut self, data: &[u8]) -> Result<(), Error> {
+        let mut data = data;
+        loop {
+            let (consumed, event) = self
+                .demuxer
+                .feed(data)
+                .map_err(|e| self.locate(e.into(), FieldKind::TagData))?;
+            data = &data[consumed..];
+            match event {
+                Some(Event::PayloadChunk(_)) => {}
+                Some(Event::TagEnd) => return Ok(()),
+                event => unreachable!("{:?} in a tag body", event),
+            }
+        }
+    }
+
+    /// The field to decode next
+    fn field(&self) -> FieldKind {
+        match self.tag_header {
+            Some(_) => FieldKind::TagData,
+            None => self.demuxer.field(),
+        }
+    }
+
+    /// Whether no byte of the next field was decoded, outside of a tag body
+    fn at_boundary(&self) -> bool {
+        match self.field() {
+            FieldKind::Header => self.position == 0,
+            FieldKind::PreviousTagSize | FieldKind::TagHeader => true,
+            _ => false,
+        }
+    }
+
+    /// The location of `field`, the next one to decode
+    fn location(&self, field: FieldKind) -> Location {
+        let (offset, tag_index) = match field {
+            FieldKind::Header => (0, None),
+            FieldKind::TagHeader => (self.position, Some(self.tag_count)),
+            _ => (self.position, self.tag_count.checked_sub(1)),
+        };
+
+        Location {
+            offset,
+            tag_index,
+            field,
+        }
+    }
+
+    /// Attaches the location of `field`, the next one to decode, to `error`.
+    fn locate(&self, error: Error, field: FieldKind) -> Error {
+        error.at(self.location(field))
+    }
+}
+
+impl Default for FlvCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for FlvCodec {
+    type Item = Tag<Bytes>;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            if let Some(header) = self.tag_header {
+                return self.decode_body(header, src);
+            }
+
+            let needed = self.demuxer.needed();
+            let field = self.field();
+            // past the first bytes of the header, the extension is left
+            if field == FieldKind::Header && self.position > 0 && needed > self.max_header_extension
+            {
+                let data_offset = (self.position as usize + needed) as u32;
+                return Err(self.locate(ParseError::HeaderDataOffset(data_offset).into(), field));
+            }
+            if src.len() < needed {
+                src.reserve(needed - src.len());
+                return Ok(None);
+            }
+
+            let (consumed, event) = self
+                .demuxer
+                .feed(&src[..needed])
+                .map_err(|e| self.locate(e.into(), field))?;
+            match event {
+                Some(Event::Header(header)) => self.header = Some(header),
+                Some(Event::TagHeader(header)) => {
+                    let data_size = header.data_size as usize;
+                    if data_size > self.max_data_size {
+                        return Err(self.locate(Error::DataSize(data_size), field));
+                    }
+                    let location = self.location(field);
+                    self.validator
+                        .check_tag_header(&header, location)
+                        .map_err(|e| e.at(location))?;
+                    self.tag_header = Some(header);
+                    self.tag_count += 1;
+                }
+                _ => {}
+            }
+            src.advance(consumed);
+            self.position += consumed as u64;
+        }
+    }
+
+    /// Fails with a truncation error if the stream ends inside a field.
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode(src)? {
+            Some(tag) => Ok(Some(tag)),
+            None if src.is_empty() && self.at_boundary() => Ok(None),
+            None => {
+                let field = self.field();
+                Err(self.locate(ParseError::Truncated(self.position).into(), field))
+            }
+        }
+    }
+}
+
+impl Encoder<Header> for FlvCodec {
+    type Error = Error;
+
+    /// Encodes the file header padded with zeros up to `data_offset`, then
+    /// PreviousTagSize0. The padding is limited as set by
+    /// [`FlvCodec::with_max_header_extension`].
+    fn encode(&mut self, header: Header, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let data_offset = header.data_offset as usize;
+        if data_offset < Header::SIZE || data_offset - Header::SIZE > self.max_header_extension {
+            return Err(ParseError::HeaderDataOffset(header.data_offset).into());
+        }
+
+        let buffer: [u8; Header::SIZE] = header.into();
+        dst.reserve(data_offset + 4);
+        dst.put_slice(&buffer);
+        dst.put_bytes(0, data_offset - Header::SIZE);
+        dst.put_u32(0);
+
+        Ok(())
+    }
+}
+
+impl<D: AsRef<[u8]>> Encoder<Tag<D>> for FlvCodec {
+    type Error = Error;
+
+    /// Encodes a tag and its PreviousTagSize, the data size in the tag header
+    /// is ignored.
+    fn encode(&mut self, tag: Tag<D>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let (header, data) = match &tag.data {
+            TagData::Audio(audio) => (Some(u8::from(audio.header)), audio.data.as_ref()),
+            TagData::Video(video) => (Some(u8::from(video.header)), video.data.as_ref()),
+            TagData::ScriptData(ScriptData { data }) | TagData::Reserved(data) => {
+                (None, data.as_ref())
+            }
+        };
+
+        let data_size = header.iter().len() + data.len();
+        if data_size > self.max_data_size {
+            return Err(Error::DataSize(data_size));
+        }
+
+        let tag_header = <[u8; TagHeader::SIZE]>::try_from(TagHeader {
+            data_size: data_size as u32,
+            ..tag.header
+        })?;
+
+        dst.reserve(TagHeader::SIZE + data_size + 4);
+        dst.put_slice(&tag_header);
+        dst.extend(header);
+        dst.put_slice(data);
+        dst.put_u32((TagHeader::SIZE + data_size) as u32);
+
+        Ok(())
+    }
+}
+
+#[test]
+fn decode_partial_frames() {
+    use crate::{TagType, VideoCodecId, VideoData, VideoDataHeader, VideoFrameType};
+
+    let mut codec = FlvCodec::new();
+    let mut encoded = BytesMut::new();
+    codec
+        .encode(
+            Header {
+                version: 1,
+                audio_flag: false,
+                video_flag: true,
+                data_offset: 12,
+            },
+            &mut encoded,
+        )
+        .unwrap();
+    for i in 0..3 {
+        let tag = Tag {
+            header: TagHeader {
+                reserved: 0,
+                tag_type: TagType::Video,
+                filter: false,
+                data_size: 0,
+                timestamp: i * 40,
+                stream_id: 0,
+            },
+            data: TagData::Video(VideoData {
+                header: VideoDataHeader {
+                    frame_type: VideoFrameType::KeyFrame,
+                    codec_id: VideoCodecId::AVC,
+                },
+                data: [i as u8; 20],
+            }),
+        };
+        codec.encode(tag, &mut encoded).unwrap();
+    }
+
+    // one byte at a time
+    let mut codec = FlvCodec::new();
+    let mut src = BytesMut::new();
+    let mut tags = vec![];
+    for &b in encoded.iter() {
+        src.put_u8(b);
+        while let Some(tag) = codec.decode(&mut src).unwrap() {
+            tags.push(tag);
+        }
+    }
+
+    assert_eq!(3, codec.header_extension().len());
+    assert!(codec.header().unwrap().video_flag);
+    assert_eq!(3, tags.len());
+    for (i, tag) in tags.iter().enumerate() {
+        assert_eq!(21, tag.header.data_size);
+        match &tag.data {
+            TagData::Video(video) => assert_eq!(&[i as u8; 20][..], &video.data[..]),
+            _ => panic!("not a video tag"),
+        }
+    }
+    assert!(src.is_empty());
+
+    // too big to be buffered
+    let mut codec = FlvCodec::new().with_max_data_size(20);
+    let mut src = encoded.clone();
+    let e = codec.decode(&mut src).unwrap_err();
+    assert!(matches!(e.inner(), Error::DataSize(21)));
+    assert_eq!(
+        Some(&Location {
+            offset: 16,
+            tag_index: Some(0),
+            field: FieldKind::TagHeader,
+        }),
+        e.location()
+    );
+
+    // the stream ends in the last tag
+    let mut codec = FlvCodec::new();
+    let mut src = encoded.clone();
+    src.truncate(encoded.len() - 5);
+    let mut decoded = 0;
+    let e = loop {
+        match codec.decode_eof(&mut src) {
+            Ok(Some(_)) => decoded += 1,
+            Ok(None) => panic!("truncation not detected"),
+            Err(e) => break e,
+        }
+    };
+    assert_eq!(2, decoded);
+    assert!(e.is_truncated());
+    assert_eq!(Some(2), e.location().unwrap().tag_index);
+}
+
+#[test]
+fn limit_header_extension() {
+    let header = |data_offset| Header {
+        version: 1,
+        audio_flag: true,
+        video_flag: true,
+        data_offset,
+    };
+
+    let mut codec = FlvCodec::new().with_max_header_extension(16);
+    let mut encoded = BytesMut::new();
+    codec.encode(header(25), &mut encoded).unwrap();
+    assert!(codec.encode(header(26), &mut encoded).is_err());
+
+    let mut src = BytesMut::from(&b"FLV\x01\x05\xff\xff\xff\xff"[..]);
+    let e = FlvCodec::new().decode(&mut src).unwrap_err();
+    assert!(matches!(
+        e.inner(),
+        Error::Parse(ParseError::HeaderDataOffset(u32::MAX))
+    ));
+    assert_eq!(FieldKind::Header, e.location().unwrap().field);
+    assert!(src.capacity() < 1 << 16);
+}
+
+#[test]
+fn decode_with_mode() {
+    use crate::{TagType, VideoCodecId, VideoData, VideoDataHeader, VideoFrameType};
+
+    // a tag with a non-zero stream id and an unknown codec id
+    let mut encoded = BytesMut::new();
+    let mut codec = FlvCodec::new();
+    codec
+        .encode(
+            Header {
+                version: 1,
+                audio_flag: false,
+                video_flag: true,
+                data_offset: 9,
+            },
+            &mut encoded,
+        )
+        .unwrap();
+    let tag = Tag {
+        header: TagHeader {
+            reserved: 0,
+            tag_type: TagType::Video,
+            filter: false,
+            data_size: 0,
+            timestamp: 0,
+            stream_id: 1,
+        },
+        data: TagData::Video(VideoData {
+            header: VideoDataHeader {
+                frame_type: VideoFrameType::KeyFrame,
+                codec_id: VideoCodecId::Unknown(15),
+            },
+            data: [0; 4],
+        }),
+    };
+    codec.encode(tag, &mut encoded).unwrap();
+
+    let e = FlvCodec::new().decode(&mut encoded.clone()).unwrap_err();
+    assert!(matches!(e.inner(), Error::Parse(ParseError::StreamId(1))));
+    assert_eq!(FieldKind::TagHeader, e.location().unwrap().field);
+
+    let mut codec = FlvCodec::with_mode(ParseMode::Lenient);
+    let tag = codec.decode(&mut encoded.clone()).unwrap().unwrap();
+    assert_eq!(1, tag.header.stream_id);
+    let anomalies: Vec<_> = codec
+        .take_warnings()
+        .into_iter()
+        .map(|warning| (warning.location.field, warning.anomaly))
+        .collect();
+    assert_eq!(
+        vec![
+            (FieldKind::TagHeader, ParseError::StreamId(1)),
+            (FieldKind::TagData, ParseError::VideoCodecId(15)),
+        ],
+        anomalies
+    );
+    assert!(codec.warnings().is_empty());
+
+    let mut codec = FlvCodec::with_mode(ParseMode::Raw);
+    assert!(codec.decode(&mut encoded.clone()).unwrap().is_some());
+    assert!(codec.warnings().is_empty());
+}
@@ -1,8 +1,9 @@
-use crate::error::ParseError;
-use std::convert::TryFrom;
+use crate::error::{Error, ParseError};
+use core::convert::TryFrom;
 
 /// FLV file header
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Header {
     /// File version (for example, 0x01 for FLV version 1)
     pub version: u8,
@@ -15,12 +16,18 @@ pub struct Header {
 
     /// The DataOffset field usually has a value of 9 for FLV version 1.
     /// This field is present to accommodate larger headers in future versions.
+    /// It is never less than [`Header::SIZE`].
     pub data_offset: u32,
 }
 
 impl Header {
     pub const SIGNATURE: [u8; 3] = [b'F', b'L', b'V'];
     pub const SIZE: usize = 9;
+
+    /// Largest header extension, the bytes between [`Header::SIZE`] and
+    /// `data_offset`, that the writers pad with zeros and that the readers
+    /// keep by default
+    pub const MAX_EXTENSION_SIZE: usize = 64 << 10;
 }
 
 impl TryFrom<[u8; Header::SIZE]> for Header {
@@ -42,6 +49,9 @@ impl TryFrom<[u8; Header::SIZE]> for Header {
         let audio_flag = 0b00000100 & flag != 0;
         let video_flag = 0b00000001 & flag != 0;
         let data_offset = u32::from_be_bytes([d1, d2, d3, d4]);
+        if (data_offset as usize) < Self::SIZE {
+            return Err(ParseError::HeaderDataOffset(data_offset));
+        }
 
         Ok(Self {
             version,
@@ -88,10 +98,21 @@ fn parse_header() {
 
     assert_eq!(Ok(header), parsed);
     assert_eq!(Ok(bytes), parsed.map(|h| h.into()));
+
+    let bytes: [u8; Header::SIZE] = Header {
+        data_offset: 8,
+        ..header
+    }
+    .into();
+    assert_eq!(
+        Err(ParseError::HeaderDataOffset(8)),
+        Header::try_from(bytes)
+    );
 }
 
 /// Flv tag type
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TagType {
     Audio,        // 8
     Video,        // 9
@@ -99,6 +120,17 @@ pub enum TagType {
     Reserved(u8), // all others
 }
 
+impl From<u8> for TagType {
+    fn from(tt: u8) -> Self {
+        match tt {
+            8 => TagType::Audio,
+            9 => TagType::Video,
+            18 => TagType::ScriptData,
+            n => TagType::Reserved(n),
+        }
+    }
+}
+
 impl From<TagType> for u8 {
     fn from(tt: TagType) -> Self {
         match tt {
@@ -112,26 +144,62 @@ impl From<TagType> for u8 {
 
 /// Flv tag header
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TagHeader {
+    /// The two reserved bits before the filter bit, 0 in valid files
+    pub reserved: u8,
     pub tag_type: TagType,
+
+    /// The tag body is preceded by an encryption header and filter params,
+    /// see [`EncryptionFilter`](crate::EncryptionFilter)
+    pub filter: bool,
     pub data_size: u32,
     pub timestamp: i32, // UI24 + UI8 => SI32
-                        // pub stream_id: u32, // UI24 always 0
+
+    /// UI24, always 0 in FLV files. When tags are used as RTMP message
+    /// bodies this is the message stream id.
+    pub stream_id: u32,
 }
 
 impl TagHeader {
     pub const SIZE: usize = (8 + 24 + 24 + 8 + 24) / 8;
     pub const MAX_DATA_SIZE: usize = 0x00ffffff; // u24::max_value()
+    pub const FILTER_BIT: u8 = 0b0010_0000;
+    pub const TAG_TYPE_MASK: u8 = 0b0001_1111;
+    pub const MAX_STREAM_ID: u32 = 0x00ffffff;
+
+    /// Builds a tag header from the fields of an RTMP message header, whose
+    /// layout is the one of a tag header.
+    pub fn from_rtmp_message(
+        message_type_id: u8,
+        payload_length: u32,
+        timestamp: u32,
+        message_stream_id: u32,
+    ) -> Result<Self, Error> {
+        if payload_length as usize > Self::MAX_DATA_SIZE {
+            return Err(Error::DataSize(payload_length as usize));
+        }
+        if message_stream_id > Self::MAX_STREAM_ID {
+            return Err(ParseError::StreamId(message_stream_id).into());
+        }
+
+        Ok(Self {
+            reserved: 0,
+            tag_type: TagType::from(message_type_id),
+            filter: false,
+            data_size: payload_length,
+            timestamp: timestamp as i32,
+            stream_id: message_stream_id,
+        })
+    }
 }
 
 impl From<[u8; TagHeader::SIZE]> for TagHeader {
-    fn from([tt, s1, s2, s3, t1, t2, t3, t0, _, _, _]: [u8; TagHeader::SIZE]) -> Self {
-        let tag_type = match tt {
-            8 => TagType::Audio,
-            9 => TagType::Video,
-            18 => TagType::ScriptData,
-            n => TagType::Reserved(n),
-        };
+    fn from([tt, s1, s2, s3, t1, t2, t3, t0, i1, i2, i3]: [u8; TagHeader::SIZE]) -> Self {
+        // UB[2] reserved, UB[1] filter, UB[5] tag type
+        let reserved = tt >> 6;
+        let filter = tt & TagHeader::FILTER_BIT != 0;
+        let tag_type = TagType::from(tt & TagHeader::TAG_TYPE_MASK);
 
         // UI24 big endian
         let data_size = u32::from_be_bytes([0, s1, s2, s3]);
@@ -145,50 +213,144 @@ impl From<[u8; TagHeader::SIZE]> for TagHeader {
         // has a timestamp of 0.
         let timestamp = i32::from_be_bytes([t0, t1, t2, t3]);
 
+        let stream_id = u32::from_be_bytes([0, i1, i2, i3]);
+
         TagHeader {
+            reserved,
             tag_type,
+            filter,
             data_size,
             timestamp,
+            stream_id,
         }
     }
 }
 
-impl From<TagHeader> for [u8; TagHeader::SIZE] {
-    fn from(h: TagHeader) -> Self {
-        let tt = h.tag_type.into();
+/// Fails if a field doesn't fit in its bits, rather than encoding another
+/// tag header.
+impl TryFrom<TagHeader> for [u8; TagHeader::SIZE] {
+    type Error = Error;
+
+    fn try_from(h: TagHeader) -> Result<Self, Error> {
+        let tag_type = u8::from(h.tag_type);
+        if tag_type > TagHeader::TAG_TYPE_MASK {
+            return Err(Error::TagType(tag_type));
+        }
+        if h.reserved > 0b11 {
+            return Err(Error::TagReserved(h.reserved));
+        }
+        if h.data_size as usize > TagHeader::MAX_DATA_SIZE {
+            return Err(Error::DataSize(h.data_size as usize));
+        }
+        if h.stream_id > TagHeader::MAX_STREAM_ID {
+            return Err(ParseError::StreamId(h.stream_id).into());
+        }
+
+        let filter = if h.filter { TagHeader::FILTER_BIT } else { 0 };
+        let tt = h.reserved << 6 | filter | tag_type;
 
         let [_, s1, s2, s3] = h.data_size.to_be_bytes();
         let [t0, t1, t2, t3] = h.timestamp.to_be_bytes();
+        let [_, i1, i2, i3] = h.stream_id.to_be_bytes();
 
-        [tt, s1, s2, s3, t1, t2, t3, t0, 0, 0, 0]
+        Ok([tt, s1, s2, s3, t1, t2, t3, t0, i1, i2, i3])
     }
 }
 
+#[test]
+fn parse_tag_header() {
+    let header = TagHeader {
+        reserved: 0,
+        tag_type: TagType::Video,
+        filter: true,
+        data_size: 0x123456,
+        timestamp: 0x7f123456,
+        stream_id: 1,
+    };
+
+    let bytes = <[u8; TagHeader::SIZE]>::try_from(header).unwrap();
+    assert_eq!(
+        [0x29, 0x12, 0x34, 0x56, 0x12, 0x34, 0x56, 0x7f, 0, 0, 1],
+        bytes
+    );
+    assert_eq!(header, TagHeader::from(bytes));
+
+    let message = TagHeader::from_rtmp_message(9, 0x123456, 0x7f123456, 1).unwrap();
+    assert_eq!(TagType::Video, message.tag_type);
+    assert_eq!(1, message.stream_id);
+    assert!(matches!(
+        TagHeader::from_rtmp_message(9, 0, 0, 0x01000000),
+        Err(Error::Parse(ParseError::StreamId(0x01000000)))
+    ));
+    assert!(matches!(
+        TagHeader::from_rtmp_message(9, 0x01000000, 0, 0),
+        Err(Error::DataSize(0x01000000))
+    ));
+
+    let reserved = TagHeader::from([0xc8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(TagType::Audio, reserved.tag_type);
+    assert_eq!(3, reserved.reserved);
+    assert!(!reserved.filter);
+    assert_eq!(
+        0xc8,
+        <[u8; TagHeader::SIZE]>::try_from(reserved).unwrap()[0]
+    );
+
+    let reserved = TagHeader::from([0x1f, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(TagType::Reserved(0x1f), reserved.tag_type);
+
+    // fields that don't fit are rejected, not masked
+    let encode = |header| <[u8; TagHeader::SIZE]>::try_from(header);
+    let out_of_range = TagHeader {
+        tag_type: TagType::Reserved(0x20),
+        ..reserved
+    };
+    assert!(matches!(encode(out_of_range), Err(Error::TagType(0x20))));
+    let out_of_range = TagHeader {
+        reserved: 4,
+        ..reserved
+    };
+    assert!(matches!(encode(out_of_range), Err(Error::TagReserved(4))));
+    let out_of_range = TagHeader {
+        stream_id: 0x01000000,
+        ..reserved
+    };
+    assert!(matches!(
+        encode(out_of_range),
+        Err(Error::Parse(ParseError::StreamId(0x01000000)))
+    ));
+}
+
 /// Sound format
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SoundFormat {
-    LinearPCMPlatformEndian = 0,
-    ADPCM = 1,
-    MP3 = 2,
-    LinearPCMLittleEndian = 3,
-    Nellymoser16 = 4,
-    Nellymoser8 = 5,
-    Nellymoser = 6,
-    G711ALaw = 7,
-    G711MuLaw = 8,
-    Reserved = 9,
-    AAC = 10,
-    Speex = 11,
-    MP38kHz = 14,
-    DeviceSpecific = 15,
-}
-
-impl TryFrom<u8> for SoundFormat {
-    type Error = ParseError;
-
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
+    LinearPCMPlatformEndian,
+    ADPCM,
+    MP3,
+    LinearPCMLittleEndian,
+    Nellymoser16,
+    Nellymoser8,
+    Nellymoser,
+    G711ALaw,
+    G711MuLaw,
+    Reserved,
+    AAC,
+    Speex,
+    MP38kHz,
+    DeviceSpecific,
+
+    /// A value undefined by the specification, such as 12 or 13, kept by the
+    /// lenient parsing mode
+    Unknown(u8),
+}
+
+impl SoundFormat {
+    /// The sound format in the high nibble of `value`, unknown values
+    /// included
+    pub fn from_byte(value: u8) -> Self {
         use SoundFormat::*;
-        Ok(match (value & 0b_1111_0000) >> 4 {
+        match (value & 0b_1111_0000) >> 4 {
             0 => LinearPCMPlatformEndian,
             1 => ADPCM,
             2 => MP3,
@@ -203,18 +365,48 @@ impl TryFrom<u8> for SoundFormat {
             11 => Speex,
             14 => MP38kHz,
             15 => DeviceSpecific,
-            n => return Err(ParseError::SoundFormat(n)),
-        })
+            n => Unknown(n),
+        }
+    }
+}
+
+impl TryFrom<u8> for SoundFormat {
+    type Error = ParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match Self::from_byte(value) {
+            SoundFormat::Unknown(n) => Err(ParseError::SoundFormat(n)),
+            sound_format => Ok(sound_format),
+        }
     }
 }
 
 impl From<SoundFormat> for u8 {
     fn from(sf: SoundFormat) -> Self {
-        (sf as u8) << 4
+        use SoundFormat::*;
+        let value = match sf {
+            LinearPCMPlatformEndian => 0,
+            ADPCM => 1,
+            MP3 => 2,
+            LinearPCMLittleEndian => 3,
+            Nellymoser16 => 4,
+            Nellymoser8 => 5,
+            Nellymoser => 6,
+            G711ALaw => 7,
+            G711MuLaw => 8,
+            Reserved => 9,
+            AAC => 10,
+            Speex => 11,
+            MP38kHz => 14,
+            DeviceSpecific => 15,
+            Unknown(n) => n & 0xf,
+        };
+        value << 4
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SoundRate {
     R5p5kHz = 0,
     R11kHz = 1,
@@ -222,18 +414,24 @@ pub enum SoundRate {
     R44kHz = 3,
 }
 
-impl TryFrom<u8> for SoundRate {
-    type Error = ParseError;
-
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
+impl SoundRate {
+    /// The sound rate in bits 2 and 3 of `value`
+    pub fn from_byte(value: u8) -> Self {
         use SoundRate::*;
-        Ok(match (value & 0b_0000_1100) >> 2 {
+        match (value & 0b_0000_1100) >> 2 {
             0 => R5p5kHz,
             1 => R11kHz,
             2 => R22kHz,
-            3 => R44kHz,
-            n => return Err(ParseError::SoundRate(n)),
-        })
+            _ => R44kHz,
+        }
+    }
+}
+
+impl TryFrom<u8> for SoundRate {
+    type Error = ParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self::from_byte(value))
     }
 }
 
@@ -244,21 +442,28 @@ impl From<SoundRate> for u8 {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SoundSize {
     S8Bit = 0,
     S16Bit = 1,
 }
 
+impl SoundSize {
+    /// The sound size in bit 1 of `value`
+    pub fn from_byte(value: u8) -> Self {
+        use SoundSize::*;
+        match (value & 0b_0000_0010) >> 1 {
+            0 => S8Bit,
+            _ => S16Bit,
+        }
+    }
+}
+
 impl TryFrom<u8> for SoundSize {
     type Error = ParseError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        use SoundSize::*;
-        Ok(match (value & 0b_0000_0010) >> 1 {
-            0 => S8Bit,
-            1 => S16Bit,
-            n => return Err(ParseError::SoundSize(n)),
-        })
+        Ok(Self::from_byte(value))
     }
 }
 
@@ -269,21 +474,28 @@ impl From<SoundSize> for u8 {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SoundType {
     Mono = 0,
     Stereo = 1,
 }
 
+impl SoundType {
+    /// The sound type in bit 0 of `value`
+    pub fn from_byte(value: u8) -> Self {
+        use SoundType::*;
+        match value & 0b_0000_0001 {
+            0 => Mono,
+            _ => Stereo,
+        }
+    }
+}
+
 impl TryFrom<u8> for SoundType {
     type Error = ParseError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        use SoundType::*;
-        Ok(match value & 0b_0000_0001 {
-            0 => Mono,
-            1 => Stereo,
-            n => return Err(ParseError::SoundType(n)),
-        })
+        Ok(Self::from_byte(value))
     }
 }
 
@@ -294,6 +506,7 @@ impl From<SoundType> for u8 {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AudioDataHeader {
     pub sound_format: SoundFormat,
     pub sound_rate: SoundRate,
@@ -301,21 +514,34 @@ pub struct AudioDataHeader {
     pub sound_type: SoundType,
 }
 
+impl AudioDataHeader {
+    /// Parses `value`, keeping an unknown sound format, see
+    /// [`AudioDataHeader::validate`].
+    pub fn from_byte(value: u8) -> Self {
+        Self {
+            sound_format: SoundFormat::from_byte(value),
+            sound_rate: SoundRate::from_byte(value),
+            sound_size: SoundSize::from_byte(value),
+            sound_type: SoundType::from_byte(value),
+        }
+    }
+
+    /// Fails if a field has an unknown value.
+    pub fn validate(&self) -> Result<(), ParseError> {
+        match self.sound_format {
+            SoundFormat::Unknown(n) => Err(ParseError::SoundFormat(n)),
+            _ => Ok(()),
+        }
+    }
+}
+
 impl TryFrom<u8> for AudioDataHeader {
     type Error = ParseError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        let sound_format = SoundFormat::try_from(value)?;
-        let sound_rate = SoundRate::try_from(value)?;
-        let sound_size = SoundSize::try_from(value)?;
-        let sound_type = SoundType::try_from(value)?;
-
-        Ok(Self {
-            sound_format,
-            sound_rate,
-            sound_size,
-            sound_type,
-        })
+        let header = Self::from_byte(value);
+        header.validate()?;
+        Ok(header)
     }
 }
 
@@ -329,54 +555,81 @@ impl From<AudioDataHeader> for u8 {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VideoFrameType {
-    KeyFrame = 1,
-    InterFrame = 2,
-    DisposableInterFrame = 3,
-    GeneratedKeyFrame = 4,
-    VideoInfoOrCommandFrame = 5,
-}
+    KeyFrame,
+    InterFrame,
+    DisposableInterFrame,
+    GeneratedKeyFrame,
+    VideoInfoOrCommandFrame,
 
-impl TryFrom<u8> for VideoFrameType {
-    type Error = ParseError;
+    /// A value undefined by the specification, such as 0, kept by the
+    /// lenient parsing mode
+    Unknown(u8),
+}
 
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
+impl VideoFrameType {
+    /// The frame type in the high nibble of `value`, unknown values included
+    pub fn from_byte(value: u8) -> Self {
         use VideoFrameType::*;
-        Ok(match (value & 0xf0) >> 4 {
+        match (value & 0xf0) >> 4 {
             1 => KeyFrame,
             2 => InterFrame,
             3 => DisposableInterFrame,
             4 => GeneratedKeyFrame,
             5 => VideoInfoOrCommandFrame,
-            n => return Err(ParseError::VideoFrameType(n)),
-        })
+            n => Unknown(n),
+        }
+    }
+}
+
+impl TryFrom<u8> for VideoFrameType {
+    type Error = ParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match Self::from_byte(value) {
+            VideoFrameType::Unknown(n) => Err(ParseError::VideoFrameType(n)),
+            frame_type => Ok(frame_type),
+        }
     }
 }
 
 impl From<VideoFrameType> for u8 {
     fn from(vft: VideoFrameType) -> Self {
-        (vft as u8) << 4
+        use VideoFrameType::*;
+        let value = match vft {
+            KeyFrame => 1,
+            InterFrame => 2,
+            DisposableInterFrame => 3,
+            GeneratedKeyFrame => 4,
+            VideoInfoOrCommandFrame => 5,
+            Unknown(n) => n & 0xf,
+        };
+        value << 4
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VideoCodecId {
-    JPEG = 1,
-    SorensonH263 = 2,
-    ScreenVideo = 3,
-    On2VP6 = 4,
-    On2VP6WithAlpha = 5,
-    ScreenVideoVersion2 = 6,
-    AVC = 7,
-    // AV1 = 13,
-}
-
-impl TryFrom<u8> for VideoCodecId {
-    type Error = ParseError;
-
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
+    JPEG,
+    SorensonH263,
+    ScreenVideo,
+    On2VP6,
+    On2VP6WithAlpha,
+    ScreenVideoVersion2,
+    AVC,
+
+    /// A value undefined by the specification, such as 12 for HEVC in some
+    /// Chinese extensions, kept by the lenient parsing mode
+    Unknown(u8),
+}
+
+impl VideoCodecId {
+    /// The codec id in the low nibble of `value`, unknown values included
+    pub fn from_byte(value: u8) -> Self {
         use VideoCodecId::*;
-        Ok(match value & 0xf {
+        match value & 0xf {
             1 => JPEG,
             2 => SorensonH263,
             3 => ScreenVideo,
@@ -384,35 +637,72 @@ impl TryFrom<u8> for VideoCodecId {
             5 => On2VP6WithAlpha,
             6 => ScreenVideoVersion2,
             7 => AVC,
-            // 13 => AV1,
-            n => return Err(ParseError::VideoCodecId(n)),
-        })
+            n => Unknown(n),
+        }
+    }
+}
+
+impl TryFrom<u8> for VideoCodecId {
+    type Error = ParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match Self::from_byte(value) {
+            VideoCodecId::Unknown(n) => Err(ParseError::VideoCodecId(n)),
+            codec_id => Ok(codec_id),
+        }
     }
 }
 
 impl From<VideoCodecId> for u8 {
     fn from(vci: VideoCodecId) -> Self {
-        vci as u8
+        use VideoCodecId::*;
+        match vci {
+            JPEG => 1,
+            SorensonH263 => 2,
+            ScreenVideo => 3,
+            On2VP6 => 4,
+            On2VP6WithAlpha => 5,
+            ScreenVideoVersion2 => 6,
+            AVC => 7,
+            Unknown(n) => n & 0xf,
+        }
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VideoDataHeader {
     pub frame_type: VideoFrameType,
     pub codec_id: VideoCodecId,
 }
 
+impl VideoDataHeader {
+    /// Parses `value`, keeping unknown values, see
+    /// [`VideoDataHeader::validate`].
+    pub fn from_byte(value: u8) -> Self {
+        Self {
+            frame_type: VideoFrameType::from_byte(value),
+            codec_id: VideoCodecId::from_byte(value),
+        }
+    }
+
+    /// Fails if a field has an unknown value.
+    pub fn validate(&self) -> Result<(), ParseError> {
+        match (self.frame_type, self.codec_id) {
+            (VideoFrameType::Unknown(n), _) => Err(ParseError::VideoFrameType(n)),
+            (_, VideoCodecId::Unknown(n)) => Err(ParseError::VideoCodecId(n)),
+            _ => Ok(()),
+        }
+    }
+}
+
 impl TryFrom<u8> for VideoDataHeader {
     type Error = ParseError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        let frame_type = VideoFrameType::try_from(value)?;
-        let codec_id = VideoCodecId::try_from(value)?;
-
-        Ok(Self {
-            frame_type,
-            codec_id,
-        })
+        let header = Self::from_byte(value);
+        header.validate()?;
+        Ok(header)
     }
 }
 
@@ -429,6 +719,7 @@ impl From<VideoDataHeader> for u8 {
 /// * 0 = Start of client-side seeking video frame sequence
 /// * 1 = End of client-side seeking video frame sequence
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SeekFlag {
     Start = 0,
     End = 1,
@@ -453,18 +744,3 @@ impl From<SeekFlag> for u8 {
         sf as u8
     }
 }
-
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
-pub struct MetaData {
-    pub duration: f64,
-    pub width: f64,
-    pub height: f64,
-    pub videodatarate: f64,
-    pub framerate: f64,
-    pub videocodecid: f64,
-    pub audiosamplerate: f64,
-    pub audiosamplesize: f64,
-    pub stereo: bool,
-    pub audiocodecid: f64,
-    pub filesize: f64,
-}
@@ -0,0 +1,310 @@
+//! The part of the `FlvWriter`s shared by every backend, which encodes into
+//! a buffer that the backends write out.
+#![cfg(any(feature = "io-std", feature = "io-tokio", feature = "io-futures"))]
+
+use crate::amf0::Value;
+use crate::script::encode_message;
+use crate::tag::is_metadata;
+use crate::{
+    AudioDataHeader, Error, Header, MetaData, MetaDataBuilder, ParseError, Result, ScriptData,
+    ScriptMessage, Tag, TagData, TagHeader, TagType, VideoDataHeader,
+};
+use std::convert::TryFrom;
+
+pub(crate) struct WriterCore {
+    /// encoded bytes to be written out by the backend
+    pub(crate) buffer: Vec<u8>,
+
+    /// bytes encoded so far
+    position: u64,
+    header: Option<(u64, Header)>,
+    reserved_metadata: Option<u64>,
+    metadata: MetaDataBuilder,
+}
+
+impl WriterCore {
+    pub(crate) fn new() -> Self {
+        Self {
+            buffer: vec![],
+            position: 0,
+            header: None,
+            reserved_metadata: None,
+            metadata: MetaDataBuilder::new(),
+        }
+    }
+
+    pub(crate) fn write_header(&mut self, header: Header) -> Result<u64> {
+        let data_offset = header.data_offset as usize;
+        if data_offset < Header::SIZE || data_offset - Header::SIZE > Header::MAX_EXTENSION_SIZE {
+            return Err(ParseError::HeaderDataOffset(header.data_offset).into());
+        }
+
+        let extension = vec![0u8; header.data_offset as usize - Header::SIZE];
+        self.write_header_with_extension(header, &extension)
+    }
+
+    pub(crate) fn write_header_with_extension(
+        &mut self,
+        header: Header,
+        extension: &[u8],
+    ) -> Result<u64> {
+        if extension.len() > (u32::MAX as usize - Header::SIZE) {
+            return Err(Error::DataSize(extension.len()));
+        }
+
+        let header = Header {
+            data_offset: (Header::SIZE + extension.len()) as u32,
+            ..header
+        };
+
+        let buffer: [u8; Header::SIZE] = header.into();
+        self.buffer.extend_from_slice(&buffer);
+        self.buffer.extend_from_slice(extension);
+
+        // PreviousTagSize0 is 0u32
+        self.buffer.extend_from_slice(&[0, 0, 0, 0]);
+
+        let written = header.data_offset as u64 + 4;
+        self.header = Some((self.position, header));
+        self.position += written;
+
+        Ok(written)
+    }
+
+    pub(crate) fn write_metadata(&mut self, metadata: &MetaData) -> Result<u64> {
+        self.write_tag(0, TagType::ScriptData, &[], &metadata.encode())
+    }
+
+    pub(crate) fn write_script_message(
+        &mut self,
+        timestamp: i32,
+        message: &ScriptMessage,
+    ) -> Result<u64> {
+        self.write_tag(timestamp, TagType::ScriptData, &[], &message.encode()?)
+    }
+
+    pub(crate) fn write_script_tag(
+        &mut self,
+        timestamp: i32,
+        name: &str,
+        values: &[Value],
+    ) -> Result<u64> {
+        self.write_tag(
+            timestamp,
+            TagType::ScriptData,
+            &[],
+            &encode_message(name, values)?,
+        )
+    }
+
+    /// Encodes `data` as the whole body of a tag described by `tag_header`,
+    /// whose data size is replaced by the length of `data`. Audio and video
+    /// tags are accounted for in the metadata like in `write_whole_tag`.
+    pub(crate) fn write_raw_tag(&mut self, tag_header: TagHeader, data: &[u8]) -> Result<u64> {
+        let plain = !tag_header.filter && tag_header.stream_id == 0;
+        if let (true, Some((&header, data))) = (plain, data.split_first()) {
+            let timestamp = tag_header.timestamp;
+            match tag_header.tag_type {
+                TagType::Audio => {
+                    let header = AudioDataHeader::from_byte(header);
+                    self.metadata.audio_tag(timestamp, header, data)
+                }
+                TagType::Video => {
+                    let header = VideoDataHeader::from_byte(header);
+                    self.metadata
+                        .video_tag(timestamp, header, data, self.position)
+                }
+                _ => {}
+            }
+        }
+
+        self.write_tag_with_header(tag_header, &[], data)
+    }
+
+    pub(crate) fn reserve_metadata(&mut self) -> Result<u64> {
+        let placeholder = MetaData {
+            has_audio: true,
+            has_video: true,
+            ..MetaData::default()
+        };
+
+        self.reserved_metadata = Some(self.position + TagHeader::SIZE as u64);
+        self.write_tag(0, TagType::ScriptData, &[], &placeholder.encode_fixed())
+    }
+
+    pub(crate) fn write_tag(
+        &mut self,
+        timestamp: i32,
+        tag_type: TagType,
+        header: &[u8],
+        data: &[u8],
+    ) -> Result<u64> {
+        let tag_header = TagHeader {
+            reserved: 0,
+            tag_type,
+            filter: false,
+            data_size: 0,
+            timestamp,
+            stream_id: 0,
+        };
+
+        self.write_tag_with_header(tag_header, header, data)
+    }
+
+    /// Encodes a tag with the type, filter, timestamp and stream id of
+    /// `tag_header`, the data size is the one of `header` and `data`.
+    pub(crate) fn write_tag_with_header(
+        &mut self,
+        tag_header: TagHeader,
+        header: &[u8],
+        data: &[u8],
+    ) -> Result<u64> {
+        let data_size = header.len() + data.len();
+
+        if data_size > TagHeader::MAX_DATA_SIZE {
+            return Err(Error::DataSize(data_size));
+        }
+
+        let tag_header = TagHeader {
+            data_size: data_size as u32,
+            ..tag_header
+        };
+
+        let th_data = <[u8; TagHeader::SIZE]>::try_from(tag_header)?;
+
+        self.buffer.extend_from_slice(&th_data);
+        self.buffer.extend_from_slice(header);
+        self.buffer.extend_from_slice(data);
+
+        let previous_tag_size = (TagHeader::SIZE + data_size) as u32;
+        self.buffer
+            .extend_from_slice(&previous_tag_size.to_be_bytes());
+
+        let written = (TagHeader::SIZE + data_size + 4) as u64;
+        self.position += written;
+
+        Ok(written)
+    }
+
+    /// Encodes a tag as read, filtered tags are passed through untouched.
+    pub(crate) fn write_whole_tag<D: AsRef<[u8]>>(&mut self, tag: &Tag<D>) -> Result<u64> {
+        let header = tag.header;
+        let plain = !header.filter && header.stream_id == 0;
+
+        match &tag.data {
+            TagData::Audio(audio) if plain => {
+                self.write_audio_tag(header.timestamp, audio.header, audio.data.as_ref())
+            }
+            TagData::Video(video) if plain => {
+                self.write_video_tag(header.timestamp, video.header, video.data.as_ref())
+            }
+            TagData::Audio(audio) => {
+                self.write_tag_with_header(header, &[u8::from(audio.header)], audio.data.as_ref())
+            }
+            TagData::Video(video) => {
+                self.write_tag_with_header(header, &[u8::from(video.header)], video.data.as_ref())
+            }
+            TagData::ScriptData(ScriptData { data }) | TagData::Reserved(data) => {
+                self.write_tag_with_header(header, &[], data.as_ref())
+            }
+        }
+    }
+
+    pub(crate) fn write_video_tag(
+        &mut self,
+        timestamp: i32,
+        header: VideoDataHeader,
+        data: &[u8],
+    ) -> Result<u64> {
+        self.metadata
+            .video_tag(timestamp, header, data, self.position);
+        self.write_tag(timestamp, TagType::Video, &[u8::from(header)], data)
+    }
+
+    pub(crate) fn write_audio_tag(
+        &mut self,
+        timestamp: i32,
+        header: AudioDataHeader,
+        data: &[u8],
+    ) -> Result<u64> {
+        self.metadata.audio_tag(timestamp, header, data);
+        self.write_tag(timestamp, TagType::Audio, &[u8::from(header)], data)
+    }
+
+    /// The final metadata and the patches to apply to the header and the
+    /// reserved metadata, as offsets in an output whose current position is
+    /// `end`.
+    pub(crate) fn finalize(&self, end: u64) -> (MetaData, Vec<(u64, Vec<u8>)>) {
+        let mut metadata = self.metadata.build();
+        metadata.filesize = self.position as f64;
+
+        let start = end - self.position;
+        let mut patches = vec![];
+
+        if let Some((position, header)) = self.header {
+            let header = Header {
+                audio_flag: metadata.has_audio,
+                video_flag: metadata.has_video,
+                ..header
+            };
+            let buffer: [u8; Header::SIZE] = header.into();
+            patches.push((start + position, buffer.to_vec()));
+        }
+
+        if let Some(position) = self.reserved_metadata {
+            patches.push((start + position, metadata.encode_fixed()));
+        }
+
+        (metadata, patches)
+    }
+}
+
+/// The first pass of `inject_metadata`, over the tags of the input
+#[derive(Default)]
+pub(crate) struct Injection {
+    builder: MetaDataBuilder,
+
+    /// tags to copy in the second pass, old onMetaData included
+    pub(crate) tag_count: usize,
+
+    /// offset of the next tag from the end of the new onMetaData tag
+    position: u64,
+}
+
+impl Injection {
+    pub(crate) fn scan(&mut self, tag: &Tag<Vec<u8>>) {
+        self.tag_count += 1;
+
+        if is_metadata(tag) {
+            return;
+        }
+
+        let timestamp = tag.header.timestamp;
+        match &tag.data {
+            TagData::Audio(audio) => self.builder.audio_tag(timestamp, audio.header, &audio.data),
+            TagData::Video(video) => {
+                self.builder
+                    .video_tag(timestamp, video.header, &video.data, self.position)
+            }
+            _ => {}
+        }
+
+        self.position += (TagHeader::SIZE + tag.header.data_size as usize + 4) as u64;
+    }
+
+    /// The metadata to inject in a file starting with `header`.
+    pub(crate) fn metadata(&self, header: &Header) -> MetaData {
+        // the encoded size doesn't depend on the values, so the final offsets
+        // can be computed before the tag is written
+        let mut metadata = self.builder.build();
+        let data_start =
+            (header.data_offset as usize + 4 + TagHeader::SIZE + metadata.encode().len() + 4)
+                as u64;
+        for fileposition in &mut metadata.keyframes.filepositions {
+            *fileposition += data_start as f64;
+        }
+        metadata.filesize = (data_start + self.position) as f64;
+
+        metadata
+    }
+}
@@ -0,0 +1,225 @@
+//! Runs the same fixtures through the std, tokio and futures backends.
+#![cfg(all(feature = "io-std", feature = "io-tokio", feature = "io-futures"))]
+
+use flv::{
+    AudioDataHeader, Header, MetaData, Recovery, SkippedRange, SoundFormat, SoundRate, SoundSize,
+    SoundType, Tag, TagData, VideoCodecId, VideoDataHeader, VideoFrameType,
+};
+use std::io::Cursor;
+
+type Tags = (Vec<Tag<Vec<u8>>>, Vec<SkippedRange>);
+
+fn fixture() -> Vec<u8> {
+    let audio = AudioDataHeader {
+        sound_format: SoundFormat::AAC,
+        sound_rate: SoundRate::R44kHz,
+        sound_size: SoundSize::S16Bit,
+        sound_type: SoundType::Stereo,
+    };
+    let video = |frame_type| VideoDataHeader {
+        frame_type,
+        codec_id: VideoCodecId::AVC,
+    };
+
+    let mut writer = flv::io::FlvWriter::new(vec![]);
+    writer
+        .write_header(Header {
+            version: 1,
+            audio_flag: true,
+            video_flag: true,
+            data_offset: Header::SIZE as u32,
+        })
+        .unwrap();
+    writer.write_metadata(&MetaData::default()).unwrap();
+    writer.write_audio_tag(0, audio, &[0, 0x12, 0x10]).unwrap();
+    for i in 0..40 {
+        let frame_type = if i % 10 == 0 {
+            VideoFrameType::KeyFrame
+        } else {
+            VideoFrameType::InterFrame
+        };
+        writer
+            .write_video_tag(i * 40, video(frame_type), &[1, 0, 0, 0, i as u8])
+            .unwrap();
+        writer.write_audio_tag(i * 40, audio, &[1; 50]).unwrap();
+    }
+    writer.into_inner()
+}
+
+fn fixtures() -> Vec<Vec<u8>> {
+    let complete = fixture();
+
+    let mut truncated = complete.clone();
+    truncated.truncate(complete.len() - 10);
+
+    let mut corrupted = complete.clone();
+    for b in &mut corrupted[400..500] {
+        *b = 0xAA;
+    }
+
+    vec![complete, truncated, corrupted]
+}
+
+fn read_std(input: &[u8]) -> Tags {
+    let mut reader = flv::io::FlvReader::new(input).with_recovery(Recovery::default());
+    reader.read_header().unwrap();
+
+    let mut tags = vec![];
+    while reader.read_previous_tag_size().is_ok() {
+        match reader.read_tag() {
+            Ok(Some(tag)) => tags.push(tag),
+            _ => break,
+        }
+    }
+    (tags, reader.skipped_ranges().to_vec())
+}
+
+/// Reads the tags as `Bytes` with the std backend
+fn read_std_bytes(input: &[u8]) -> Tags {
+    let mut reader = flv::io::FlvReader::new(input).with_recovery(Recovery::default());
+    reader.read_header().unwrap();
+
+    let mut tags = vec![];
+    while reader.read_previous_tag_size().is_ok() {
+        match reader.read_tag_bytes() {
+            Ok(Some(tag)) => tags.push(to_vec(tag)),
+            _ => break,
+        }
+    }
+    (tags, reader.skipped_ranges().to_vec())
+}
+
+fn to_vec<D: AsRef<[u8]>>(tag: Tag<D>) -> Tag<Vec<u8>> {
+    let data = match tag.data {
+        TagData::Audio(audio) => TagData::Audio(flv::AudioData {
+            header: audio.header,
+            data: audio.data.as_ref().to_vec(),
+        }),
+        TagData::Video(video) => TagData::Video(flv::VideoData {
+            header: video.header,
+            data: video.data.as_ref().to_vec(),
+        }),
+        TagData::ScriptData(script) => TagData::ScriptData(flv::ScriptData {
+            data: script.data.as_ref().to_vec(),
+        }),
+        TagData::Reserved(data) => TagData::Reserved(data.as_ref().to_vec()),
+    };
+    Tag {
+        header: tag.header,
+        data,
+    }
+}
+
+async fn read_tokio(input: &[u8]) -> Tags {
+    let mut reader = flv::tokio::FlvReader::new(input).with_recovery(Recovery::default());
+    reader.read_header().await.unwrap();
+
+    let mut tags = vec![];
+    while reader.read_previous_tag_size().await.is_ok() {
+        match reader.read_tag().await {
+            Ok(Some(tag)) => tags.push(tag),
+            _ => break,
+        }
+    }
+    (tags, reader.skipped_ranges().to_vec())
+}
+
+async fn read_futures(input: &[u8]) -> Tags {
+    let mut reader = flv::futures::FlvReader::new(input).with_recovery(Recovery::default());
+    reader.read_header().await.unwrap();
+
+    let mut tags = vec![];
+    while reader.read_previous_tag_size().await.is_ok() {
+        match reader.read_tag().await {
+            Ok(Some(tag)) => tags.push(tag),
+            _ => break,
+        }
+    }
+    (tags, reader.skipped_ranges().to_vec())
+}
+
+#[tokio::test]
+async fn read_parity() {
+    for input in fixtures() {
+        let expected = read_std(&input);
+        assert!(!expected.0.is_empty());
+        assert_eq!(expected, read_std_bytes(&input));
+        assert_eq!(expected, read_tokio(&input).await);
+        assert_eq!(expected, futures::executor::block_on(read_futures(&input)));
+    }
+}
+
+#[tokio::test]
+async fn inject_metadata_parity() {
+    for input in fixtures().into_iter().take(2) {
+        let mut expected = vec![];
+        let metadata = flv::io::inject_metadata(Cursor::new(&input), &mut expected).unwrap();
+
+        let mut output = vec![];
+        let read = flv::tokio::inject_metadata(Cursor::new(&input), &mut output)
+            .await
+            .unwrap();
+        assert_eq!((&metadata, &expected), (&read, &output));
+
+        let mut output = futures::io::Cursor::new(vec![]);
+        let read = futures::executor::block_on(flv::futures::inject_metadata(
+            futures::io::Cursor::new(&input),
+            &mut output,
+        ))
+        .unwrap();
+        assert_eq!((&metadata, &expected), (&read, output.get_ref()));
+    }
+}
+
+#[tokio::test]
+async fn finalize_parity() {
+    let audio = AudioDataHeader {
+        sound_format: SoundFormat::MP3,
+        sound_rate: SoundRate::R44kHz,
+        sound_size: SoundSize::S16Bit,
+        sound_type: SoundType::Stereo,
+    };
+    let header = Header {
+        version: 1,
+        audio_flag: true,
+        video_flag: true,
+        data_offset: Header::SIZE as u32,
+    };
+
+    let mut writer = flv::io::FlvWriter::new(Cursor::new(vec![]));
+    writer.write_header(header).unwrap();
+    writer.reserve_metadata().unwrap();
+    for i in 0..=100 {
+        writer.write_audio_tag(i * 20, audio, &[0; 124]).unwrap();
+    }
+    let metadata = writer.finalize().unwrap();
+    let expected = writer.into_inner().into_inner();
+
+    let mut writer = flv::tokio::FlvWriter::new(Cursor::new(vec![]));
+    writer.write_header(header).await.unwrap();
+    writer.reserve_metadata().await.unwrap();
+    for i in 0..=100 {
+        writer
+            .write_audio_tag(i * 20, audio, &[0; 124])
+            .await
+            .unwrap();
+    }
+    let finalized = writer.finalize().await.unwrap();
+    let output = writer.into_inner().into_inner();
+
+    assert_eq!(2.0, metadata.duration);
+    assert_eq!((&metadata, &expected), (&finalized, &output));
+
+    let mut writer = flv::futures::FlvWriter::new(futures::io::Cursor::new(vec![]));
+    let finalized = futures::executor::block_on(async {
+        writer.write_header(header).await?;
+        writer.reserve_metadata().await?;
+        for i in 0..=100 {
+            writer.write_audio_tag(i * 20, audio, &[0; 124]).await?;
+        }
+        writer.finalize().await
+    })
+    .unwrap();
+    let output = writer.into_inner().into_inner();
+    assert_eq!((&metadata, &expected), (&finalized, &output));
+}
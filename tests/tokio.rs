@@ -0,0 +1,140 @@
+//! The tokio backend on its own, without the std and futures backends.
+#![cfg(feature = "io-tokio")]
+
+use flv::tokio::{inject_metadata, FlvReader, FlvWriter};
+use flv::{
+    AudioDataHeader, Header, Recovery, SoundFormat, SoundRate, SoundSize, SoundType, Tag, TagData,
+    VideoCodecId, VideoDataHeader, VideoFrameType,
+};
+use std::io::Cursor;
+
+const HEADER: Header = Header {
+    version: 1,
+    audio_flag: true,
+    video_flag: true,
+    data_offset: Header::SIZE as u32,
+};
+
+const AUDIO: AudioDataHeader = AudioDataHeader {
+    sound_format: SoundFormat::MP3,
+    sound_rate: SoundRate::R44kHz,
+    sound_size: SoundSize::S16Bit,
+    sound_type: SoundType::Stereo,
+};
+
+/// Writes 20 audio and video tags, with a reserved onMetaData tag if
+/// `finalize`
+async fn fixture(finalize: bool) -> Vec<u8> {
+    let mut writer = FlvWriter::new(Cursor::new(vec![]));
+    writer.write_header(HEADER).await.unwrap();
+    if finalize {
+        writer.reserve_metadata().await.unwrap();
+    }
+    for i in 0..20 {
+        let frame_type = if i % 10 == 0 {
+            VideoFrameType::KeyFrame
+        } else {
+            VideoFrameType::InterFrame
+        };
+        let video = VideoDataHeader {
+            frame_type,
+            codec_id: VideoCodecId::AVC,
+        };
+        writer
+            .write_video_tag(i * 40, video, &[1, 0, 0, 0, i as u8])
+            .await
+            .unwrap();
+        writer
+            .write_audio_tag(i * 40, AUDIO, &[0; 50])
+            .await
+            .unwrap();
+    }
+    if finalize {
+        writer.finalize().await.unwrap();
+    }
+    writer.into_inner().into_inner()
+}
+
+async fn read_tags(reader: &mut FlvReader<&[u8]>) -> Vec<Tag<Vec<u8>>> {
+    reader.read_header().await.unwrap();
+
+    let mut tags = vec![];
+    while reader.read_previous_tag_size().await.is_ok() {
+        match reader.read_tag().await {
+            Ok(Some(tag)) => tags.push(tag),
+            _ => break,
+        }
+    }
+    tags
+}
+
+#[tokio::test]
+async fn write_and_read() {
+    let input = fixture(true).await;
+
+    let mut reader = FlvReader::new(Cursor::new(&input));
+    let metadata = reader.read_metadata().await.unwrap();
+    assert!(metadata.has_audio && metadata.has_video);
+    assert_eq!(0.76, metadata.duration);
+
+    let tags = read_tags(&mut FlvReader::new(&input[..])).await;
+    assert_eq!(41, tags.len());
+    assert!(matches!(tags[0].data, TagData::ScriptData(_)));
+    for (i, pair) in tags[1..].chunks(2).enumerate() {
+        assert_eq!(
+            [i as i32 * 40; 2],
+            [pair[0].header.timestamp, pair[1].header.timestamp]
+        );
+        match &pair[0].data {
+            TagData::Video(video) => assert_eq!(&[1, 0, 0, 0, i as u8][..], &video.data[..]),
+            _ => panic!("not a video tag"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn inject_metadata_first() {
+    let input = fixture(false).await;
+
+    let mut output = vec![];
+    let metadata = inject_metadata(Cursor::new(&input), &mut output)
+        .await
+        .unwrap();
+    assert_eq!(0.76, metadata.duration);
+
+    let mut reader = FlvReader::new(Cursor::new(&output));
+    assert_eq!(metadata, reader.read_metadata().await.unwrap());
+
+    let tags = read_tags(&mut FlvReader::new(&output[..])).await;
+    assert_eq!(read_tags(&mut FlvReader::new(&input[..])).await, tags[1..]);
+}
+
+#[tokio::test]
+async fn recover_and_truncate() {
+    let input = fixture(false).await;
+    let expected = read_tags(&mut FlvReader::new(&input[..])).await;
+
+    let mut corrupted = input.clone();
+    for b in &mut corrupted[200..300] {
+        *b = 0xAA;
+    }
+    let mut reader = FlvReader::new(&corrupted[..]).with_recovery(Recovery::default());
+    let tags = read_tags(&mut reader).await;
+    assert!(!reader.skipped_ranges().is_empty());
+    assert!(tags.len() < expected.len());
+    assert_eq!(expected[expected.len() - 1], tags[tags.len() - 1]);
+
+    let truncated = &input[..input.len() - 10];
+    let mut reader = FlvReader::new(truncated);
+    reader.read_header().await.unwrap();
+    let e = loop {
+        if let Err(e) = reader.read_previous_tag_size().await {
+            break e;
+        }
+        if let Err(e) = reader.read_tag().await {
+            break e;
+        }
+    };
+    assert!(e.is_truncated());
+    assert_eq!(Some(39), e.location().unwrap().tag_index);
+}